@@ -0,0 +1,13 @@
+create table nonces
+(
+    nonce       uuid primary key,
+    user_id     varchar(42) not null references users (user_id) on delete cascade,
+    issued_at   timestamptz not null,
+    expires_at  timestamptz not null,
+    consumed_at timestamptz
+);
+
+create index nonces_expires_at_idx on nonces (expires_at);
+
+alter table users
+    drop column nonce;
@@ -0,0 +1,12 @@
+create table refresh_tokens
+(
+    token_hash bytea primary key,
+    family_id  uuid        not null,
+    user_id    varchar(42) not null references users (user_id) on delete cascade,
+    issued_at  timestamptz not null,
+    expires_at timestamptz not null,
+    rotated_at timestamptz,
+    revoked_at timestamptz
+);
+
+create index refresh_tokens_family_id_idx on refresh_tokens (family_id);
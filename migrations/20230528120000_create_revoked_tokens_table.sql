@@ -0,0 +1,7 @@
+create table revoked_tokens
+(
+    jti        uuid primary key,
+    user_id    varchar(42) not null references users (user_id) on delete cascade,
+    expires_at timestamptz not null,
+    revoked_at timestamptz not null
+);
@@ -0,0 +1,9 @@
+alter table nonces
+    add column chain_id bigint not null default 1;
+alter table nonces
+    alter column chain_id drop default;
+
+alter table refresh_tokens
+    add column chain_id bigint not null default 1;
+alter table refresh_tokens
+    alter column chain_id drop default;
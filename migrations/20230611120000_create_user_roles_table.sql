@@ -0,0 +1,11 @@
+create table user_roles
+(
+    user_id    varchar(42) not null references users (user_id) on delete cascade,
+    role       varchar(16) not null check (role in ('player', 'moderator', 'admin', 'service')),
+    granted_at timestamptz not null default now(),
+    primary key (user_id, role)
+);
+
+insert into user_roles(user_id, role)
+select user_id, 'player'
+from users;
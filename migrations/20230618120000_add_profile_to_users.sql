@@ -0,0 +1,7 @@
+alter table users
+    add column nickname      varchar(32),
+    add column avatar_url    varchar(2048),
+    add column created_at    timestamptz not null default now(),
+    add column last_login_at timestamptz;
+
+create unique index users_nickname_idx on users (lower(nickname));
@@ -0,0 +1,70 @@
+create table accounts
+(
+    account_id    uuid primary key,
+    nickname      varchar(32),
+    avatar_url    varchar(2048),
+    created_at    timestamptz not null default now(),
+    last_login_at timestamptz
+);
+
+create unique index accounts_nickname_idx on accounts (lower(nickname));
+
+-- Every existing wallet becomes an account on its own.
+alter table users
+    add column account_id uuid;
+
+update users
+set account_id = gen_random_uuid();
+
+insert into accounts(account_id, nickname, avatar_url, created_at, last_login_at)
+select account_id, nickname, avatar_url, created_at, last_login_at
+from users;
+
+-- Wallets get an account on the first sign-in or when they are linked to an existing one.
+alter table users
+    add constraint users_account_id_fkey foreign key (account_id) references accounts (account_id)
+        on delete set null,
+    drop column nickname,
+    drop column avatar_url,
+    drop column last_login_at;
+
+create index users_account_id_idx on users (account_id);
+
+-- Roles belong to accounts.
+alter table user_roles
+    add column account_id uuid references accounts (account_id) on delete cascade;
+
+update user_roles
+set account_id = users.account_id
+from users
+where user_roles.user_id = users.user_id;
+
+alter table user_roles
+    drop constraint user_roles_pkey,
+    drop column user_id,
+    alter column account_id set not null,
+    add primary key (account_id, role);
+
+-- Tokens are issued for accounts, refresh tokens also remember the wallet used to sign in.
+alter table refresh_tokens
+    add column account_id uuid references accounts (account_id) on delete cascade;
+
+update refresh_tokens
+set account_id = users.account_id
+from users
+where refresh_tokens.user_id = users.user_id;
+
+alter table refresh_tokens
+    alter column account_id set not null;
+
+alter table revoked_tokens
+    add column account_id uuid references accounts (account_id) on delete cascade;
+
+update revoked_tokens
+set account_id = users.account_id
+from users
+where revoked_tokens.user_id = users.user_id;
+
+alter table revoked_tokens
+    drop column user_id,
+    alter column account_id set not null;
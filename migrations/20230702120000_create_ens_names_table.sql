@@ -0,0 +1,9 @@
+create table ens_names
+(
+    user_id     varchar(42) primary key references users (user_id) on delete cascade,
+    -- Null if the address doesn't have a primary name.
+    name        varchar(255),
+    resolved_at timestamptz not null
+);
+
+create index ens_names_name_idx on ens_names (lower(name));
@@ -0,0 +1,33 @@
+create table token_transfers
+(
+    contract     varchar(42) not null,
+    block_number bigint      not null,
+    log_index    bigint      not null,
+    block_hash   varchar(66) not null,
+    token_id     varchar(78) not null,
+    from_address varchar(42) not null,
+    to_address   varchar(42) not null,
+    primary key (contract, block_number, log_index)
+);
+
+create index token_transfers_token_idx on token_transfers (contract, token_id);
+
+-- Owner of every token is the recipient of its latest indexed transfer.
+create table token_owners
+(
+    contract     varchar(42) not null,
+    token_id     varchar(78) not null,
+    owner        varchar(42) not null,
+    block_number bigint      not null,
+    log_index    bigint      not null,
+    primary key (contract, token_id)
+);
+
+create index token_owners_owner_idx on token_owners (owner);
+
+create table indexer_checkpoints
+(
+    contract     varchar(42) primary key,
+    block_number bigint      not null,
+    block_hash   varchar(66) not null
+);
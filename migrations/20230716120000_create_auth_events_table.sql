@@ -0,0 +1,31 @@
+create table auth_events
+(
+    id             bigserial primary key,
+    kind           varchar(32) not null,
+    outcome        varchar(16) not null,
+    failure_reason text,
+    -- Not a reference, events outlive accounts and wallets.
+    account_id     uuid,
+    user_id        varchar(42),
+    ip             varchar(45),
+    user_agent     varchar(512),
+    request_id     varchar(64),
+    created_at     timestamptz not null default now()
+);
+
+create index auth_events_user_id_idx on auth_events (user_id, id);
+create index auth_events_created_at_idx on auth_events (created_at);
+
+-- The log is append-only.
+create function reject_auth_event_change() returns trigger as
+$$
+begin
+    raise exception 'auth_events is append-only';
+end;
+$$ language plpgsql;
+
+create trigger auth_events_append_only
+    before update or delete
+    on auth_events
+    for each row
+execute function reject_auth_event_change();
@@ -0,0 +1,5 @@
+alter table nonces
+    add column purpose    varchar(16) not null default 'sign_in',
+    add column account_id uuid references accounts (account_id) on delete cascade;
+alter table nonces
+    alter column purpose drop default;
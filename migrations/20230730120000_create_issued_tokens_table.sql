@@ -0,0 +1,11 @@
+-- Access tokens are remembered until they expire, so the tokens of a wallet can be revoked
+-- once it's unlinked from the account.
+create table issued_tokens
+(
+    jti        uuid primary key,
+    account_id uuid        not null references accounts (account_id) on delete cascade,
+    user_id    varchar(42) not null references users (user_id) on delete cascade,
+    expires_at timestamptz not null
+);
+
+create index issued_tokens_user_id_idx on issued_tokens (user_id);
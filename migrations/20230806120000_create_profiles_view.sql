@@ -0,0 +1,18 @@
+-- Account along with its linked wallets, which is what profile endpoints return.
+create view profiles as
+select account_id,
+       nickname,
+       avatar_url,
+       created_at,
+       last_login_at,
+       coalesce(
+               (select json_agg(
+                               json_build_object('address', user_id, 'ens_name', ens_names.name)
+                               order by user_id
+                           )
+                from users
+                         left join ens_names using (user_id)
+                where users.account_id = accounts.account_id),
+               '[]'
+           ) as wallets
+from accounts;
@@ -1,36 +1,97 @@
-use crate::jwt::Jwt;
+use crate::{
+    jwt::{Jwt, JwtKey},
+    nonce::{Nonce, NoncePurpose},
+};
 use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use ethers::{prelude::Address, types::transaction::eip712::TypedData};
 use eyre::{Result, WrapErr};
 use jsonwebtoken::{
     jwk::{
         AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, OctetKeyPairParameters,
         OctetKeyPairType, PublicKeyUse,
     },
-    DecodingKey, EncodingKey,
+    Algorithm, DecodingKey, EncodingKey,
 };
 use ring::signature::{Ed25519KeyPair, KeyPair};
 use secrecy::{ExposeSecret, Secret};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use siwe::{Message, TimeStamp, Version};
 use sqlx::postgres::PgConnectOptions;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
 use strum::{Display, EnumString};
+use utoipa::ToSchema;
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct MainConfig {
     pub app: AppConfig,
     pub db: DatabaseConfig,
     pub secrets: SecretsConfig,
+    pub siwe: SiweConfig,
+    pub eip712: Eip712Config,
+    pub chains: ChainsConfig,
+    pub nonce: NonceConfig,
+    pub refresh_token: RefreshTokenConfig,
+    pub revocation: RevocationConfig,
+    pub ens: EnsConfig,
+    #[serde(default)]
+    pub nft: NftConfig,
+    pub ethereum: Option<EthereumConfig>,
+    pub indexer: Option<IndexerConfig>,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    pub otlp: Option<OtlpConfig>,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub session: SessionConfig,
+    #[serde(default)]
+    pub websocket: WebSocketConfig,
+    #[serde(default)]
+    pub openapi: OpenApiConfig,
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct AppConfig {
     pub host: String,
     pub port: u16,
+    /// Public URL of the service, which is used as issuer of tokens.
+    ///
+    /// Trailing `/` is trimmed, so the URL is the same in tokens and discovery document.
+    #[serde(deserialize_with = "deserialize_base_url")]
+    pub base_url: String,
+    /// Time given to in-flight requests to complete after shutdown signal.
+    #[serde(default = "default_drain_timeout_secs")]
+    pub drain_timeout_secs: u64,
+}
+
+fn default_drain_timeout_secs() -> u64 {
+    30
+}
+
+fn deserialize_base_url<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let base_url = String::deserialize(deserializer)?;
+
+    Ok(base_url.trim_end_matches('/').to_owned())
 }
 
 impl AppConfig {
     pub fn address(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
+
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -58,38 +119,477 @@ impl DatabaseConfig {
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct SecretsConfig {
-    pub key_pair: Secret<String>,
+    /// Id of the key pair, which signs new tokens.
+    pub signing_key_id: String,
+    /// Ed25519 private keys in pkcs8v2 format encoded by base64 standard encoding, by their ids.
+    ///
+    /// Keys other than the signing one are only used to verify tokens issued before the rotation,
+    /// and can be removed once those tokens expire.
+    pub key_pairs: BTreeMap<String, Secret<String>>,
 }
 
 impl SecretsConfig {
-    pub fn jwt(&self) -> Result<Jwt> {
-        let pkcs8v2_keypair_base64_encoded = self.key_pair.expose_secret();
-        let key_pair_bytes = base64::engine::general_purpose::STANDARD
-            .decode(pkcs8v2_keypair_base64_encoded)
-            .wrap_err("Failed to decode keypair")?;
-        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&key_pair_bytes)
-            .wrap_err("Failed to create `Ed25519KeyPair` from source")?;
-        let encoding_key = EncodingKey::from_ed_der(&key_pair_bytes);
-        let public_key = key_pair.public_key().as_ref();
-        let decoding_key = DecodingKey::from_ed_der(public_key);
-        let base64encoded_public_key =
-            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(public_key);
-        let jwk = Jwk {
-            common: CommonParameters {
-                public_key_use: Some(PublicKeyUse::Signature),
-                ..Default::default()
-            },
-            algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
-                key_type: OctetKeyPairType::OctetKeyPair,
-                curve: EllipticCurve::Ed25519,
-                x: base64encoded_public_key,
-            }),
+    pub fn jwt(&self, issuer: &str) -> Result<Jwt> {
+        let keys = self
+            .key_pairs
+            .iter()
+            .map(|(id, key_pair)| {
+                jwt_key(id, key_pair).wrap_err_with(|| format!("Failed to compose key `{id}`"))
+            })
+            .collect::<Result<_>>()?;
+
+        Jwt::new(keys, &self.signing_key_id, issuer.to_owned())
+    }
+}
+
+fn jwt_key(id: &str, key_pair: &Secret<String>) -> Result<JwtKey> {
+    let pkcs8v2_keypair_base64_encoded = key_pair.expose_secret();
+    let key_pair_bytes = base64::engine::general_purpose::STANDARD
+        .decode(pkcs8v2_keypair_base64_encoded)
+        .wrap_err("Failed to decode keypair")?;
+    let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&key_pair_bytes)
+        .wrap_err("Failed to create `Ed25519KeyPair` from source")?;
+    let encoding_key = EncodingKey::from_ed_der(&key_pair_bytes);
+    let public_key = key_pair.public_key().as_ref();
+    let decoding_key = DecodingKey::from_ed_der(public_key);
+    let base64encoded_public_key =
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(public_key);
+    let jwk = Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            algorithm: Some(Algorithm::EdDSA),
+            key_id: Some(id.to_owned()),
+            ..Default::default()
+        },
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: base64encoded_public_key,
+        }),
+    };
+
+    Ok(JwtKey {
+        id: id.to_owned(),
+        encoding_key,
+        decoding_key,
+        jwk,
+    })
+}
+
+/// Parameters of EIP-4361 (Sign-In with Ethereum) messages issued by the service.
+#[derive(Deserialize, Clone, Debug)]
+pub struct SiweConfig {
+    /// RFC 3986 authority requesting the signing, e.g. `battlemon.com`.
+    pub domain: String,
+    /// RFC 3986 URI referring to the resource that is the subject of the signing.
+    pub uri: String,
+    pub statement: Option<String>,
+    /// Statement of wallet linking messages, which is followed by id of the account.
+    #[serde(default = "default_link_statement")]
+    pub link_statement: String,
+    #[serde(default)]
+    pub resources: Vec<String>,
+}
+
+impl SiweConfig {
+    /// Statement of the message, which tells the user what the signature authorizes.
+    pub fn statement(&self, purpose: NoncePurpose) -> Option<String> {
+        match purpose {
+            NoncePurpose::SignIn => self.statement.clone(),
+            NoncePurpose::LinkWallet { account_id } => {
+                Some(format!("{} {account_id}", self.link_statement))
+            }
+        }
+    }
+
+    /// Compose message, which expires together with the `nonce`.
+    pub fn message(&self, address: Address, nonce: &Nonce) -> Result<Message> {
+        let resources = self
+            .resources
+            .iter()
+            .map(|resource| {
+                resource
+                    .parse()
+                    .wrap_err_with(|| format!("Failed to parse resource `{resource}`"))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Message {
+            domain: self.domain.parse().wrap_err("Failed to parse domain")?,
+            address: address.0,
+            statement: self.statement(nonce.purpose),
+            uri: self.uri.parse().wrap_err("Failed to parse uri")?,
+            version: Version::V1,
+            chain_id: nonce.chain_id,
+            nonce: nonce.value.simple().to_string(),
+            issued_at: timestamp(nonce.issued_at)?,
+            expiration_time: Some(timestamp(nonce.expires_at)?),
+            not_before: None,
+            request_id: None,
+            resources,
+        })
+    }
+}
+
+/// Parameters of EIP-712 typed data messages issued by the service.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Eip712Config {
+    /// Name and version of the signing domain, which wallets show to the user.
+    pub name: String,
+    pub version: String,
+    pub statement: String,
+    /// Statement of wallet linking requests, which carry id of the account separately.
+    #[serde(default = "default_link_statement")]
+    pub link_statement: String,
+}
+
+impl Eip712Config {
+    /// Compose typed data of the request the `nonce` is issued for, which expires together
+    /// with the `nonce`.
+    ///
+    /// Sign-in and wallet linking requests have distinct types, so a signature of one
+    /// doesn't verify the other.
+    pub fn typed_data(&self, address: Address, nonce: &Nonce) -> Result<TypedData> {
+        let mut fields = vec![
+            json!({ "name": "statement", "type": "string" }),
+            json!({ "name": "wallet", "type": "address" }),
+        ];
+        let mut message = json!({
+            "wallet": address,
+            "nonce": nonce.value.simple().to_string(),
+            "issuedAt": nonce.issued_at.timestamp(),
+            "expiresAt": nonce.expires_at.timestamp(),
+        });
+        let primary_type = match nonce.purpose {
+            NoncePurpose::SignIn => {
+                message["statement"] = json!(self.statement);
+
+                "Login"
+            }
+            NoncePurpose::LinkWallet { account_id } => {
+                fields.push(json!({ "name": "account", "type": "string" }));
+                message["statement"] = json!(self.link_statement);
+                message["account"] = json!(account_id);
+
+                "LinkWallet"
+            }
         };
+        fields.extend([
+            json!({ "name": "nonce", "type": "string" }),
+            json!({ "name": "issuedAt", "type": "uint256" }),
+            json!({ "name": "expiresAt", "type": "uint256" }),
+        ]);
+        let typed_data = json!({
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                ],
+                primary_type: fields,
+            },
+            "primaryType": primary_type,
+            "domain": {
+                "name": self.name,
+                "version": self.version,
+                "chainId": nonce.chain_id,
+            },
+            "message": message,
+        });
+
+        serde_json::from_value(typed_data).wrap_err("Failed to compose typed data")
+    }
+}
+
+fn default_link_statement() -> String {
+    "Link this wallet to Battlemon account".to_owned()
+}
+
+/// EIP-155 chains users are allowed to sign in from.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ChainsConfig {
+    /// Chain of the sign-in request if the client doesn't specify one.
+    pub default_chain_id: u64,
+    pub allowed_chain_ids: Vec<u64>,
+}
+
+impl ChainsConfig {
+    pub fn is_allowed(&self, chain_id: u64) -> bool {
+        self.allowed_chain_ids.contains(&chain_id)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct NonceConfig {
+    pub ttl_secs: i64,
+    /// How often expired nonces, issued tokens and revocations of expired tokens are removed
+    /// from database.
+    pub sweep_interval_secs: u64,
+}
+
+impl NonceConfig {
+    pub fn ttl(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.ttl_secs)
+    }
+
+    pub fn sweep_interval(&self) -> Duration {
+        Duration::from_secs(self.sweep_interval_secs)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RefreshTokenConfig {
+    pub ttl_secs: i64,
+}
+
+impl RefreshTokenConfig {
+    pub fn ttl(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.ttl_secs)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RevocationConfig {
+    /// How long the token is considered not revoked without asking database.
+    pub cache_ttl_secs: u64,
+}
+
+impl RevocationConfig {
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl_secs)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct EnsConfig {
+    /// How long resolved names are used without asking Ethereum node again.
+    pub cache_ttl_secs: i64,
+}
+
+impl EnsConfig {
+    pub fn cache_ttl(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.cache_ttl_secs)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct EthereumConfig {
+    /// JSON-RPC endpoint of Ethereum node.
+    pub rpc_url: String,
+    pub request_timeout_ms: u64,
+}
+
+impl EthereumConfig {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_millis(self.request_timeout_ms)
+    }
+}
+
+/// NFT collections, which are checked at sign-in and can be required to access the service.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct NftConfig {
+    #[serde(default)]
+    pub collections: Vec<CollectionConfig>,
+    /// Names of collections, at least one of which the account has to hold to sign in.
+    #[serde(default)]
+    pub login_requires: Vec<String>,
+    /// Names of collections, at least one of which is required to access the route, by route paths.
+    #[serde(default)]
+    pub route_requirements: HashMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CollectionConfig {
+    /// Name of the collection, which is used in claims and requirements.
+    pub name: String,
+    pub address: Address,
+    pub standard: TokenStandard,
+    /// Whether ERC-721 contract implements enumerable extension, so owned token ids can be listed.
+    #[serde(default)]
+    pub enumerable: bool,
+    /// Ids of ERC-1155 tokens to check.
+    #[serde(default)]
+    pub token_ids: Vec<u64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenStandard {
+    Erc721,
+    Erc1155,
+}
+
+/// Indexer of `Transfer` events, which stores ownership of tokens of the contracts.
+#[derive(Deserialize, Clone, Debug)]
+pub struct IndexerConfig {
+    /// Number of blocks on top of the indexed one, which make it final enough to be indexed.
+    pub confirmations: u64,
+    pub poll_interval_ms: u64,
+    /// Maximal number of blocks requested in a single `eth_getLogs` call.
+    pub max_block_range: u64,
+    #[serde(default)]
+    pub contracts: Vec<IndexedContractConfig>,
+}
+
+impl IndexerConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct IndexedContractConfig {
+    pub address: Address,
+    /// Block the contract was deployed at, indexing starts from it.
+    #[serde(default)]
+    pub start_block: u64,
+}
+
+/// Limits of requests to the routes, which are counted in token buckets.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct RateLimitConfig {
+    /// Take client IP from the last entry of `X-Forwarded-For` header, which is appended by proxy.
+    ///
+    /// Enable only behind a trusted proxy, otherwise clients can pick any IP.
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+    /// Limits by route paths, e.g. `/users/:user_id/nonce`.
+    #[serde(default)]
+    pub routes: HashMap<String, RouteRateLimit>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct RouteRateLimit {
+    /// Limit of requests from the same client IP.
+    pub per_ip: Option<TokenBucketConfig>,
+    /// Limit of requests targeting the same address, i.e. its `user_id` or signed payload.
+    pub per_address: Option<TokenBucketConfig>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct TokenBucketConfig {
+    /// Number of requests, which can be made at once.
+    pub burst: u32,
+    /// Number of requests, which are replenished every minute.
+    pub per_minute: u32,
+}
+
+/// Exposition of Prometheus metrics at `GET /metrics`.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct MetricsConfig {
+    /// Port of the admin listener, which serves metrics instead of the public one.
+    ///
+    /// The listener is bound to the host of the app.
+    pub admin_port: Option<u16>,
+}
+
+/// Export of traces to OpenTelemetry collector over OTLP/HTTP.
+#[derive(Deserialize, Clone, Debug)]
+pub struct OtlpConfig {
+    /// Base URL of the collector, `/v1/traces` is appended to it.
+    pub endpoint: String,
+    /// Value of `service.name` resource attribute.
+    pub service_name: String,
+    /// Share of traces started by the service to sample, traces of callers follow their decision.
+    pub sampling_ratio: f64,
+}
+
+/// CORS policy for browser clients, which call the service from other origins.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct CorsConfig {
+    /// Origins like `https://battlemon.com`, `https://*.battlemon.com` for any subdomain,
+    /// or `*` for any origin.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Methods like `GET`, or `*` for any method.
+    #[serde(default)]
+    pub allowed_methods: Vec<String>,
+    /// Request headers like `authorization`, or `*` for any header.
+    #[serde(default)]
+    pub allowed_headers: Vec<String>,
+    /// Allow cookies and authorization headers, can't be used with wildcards.
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// Time browsers may cache preflight responses for.
+    pub max_age_secs: Option<u64>,
+}
+
+/// Sessions of web clients, which keep tokens in cookies instead of the storage of the page.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Allow clients to ask for tokens as cookies with `session = "cookie"`.
+    pub cookies_enabled: bool,
+    /// Send cookies over HTTPS only, disable for local development over plain HTTP.
+    pub secure: bool,
+    pub same_site: SameSitePolicy,
+    /// Domain of cookies, e.g. `battlemon.com` to share them with subdomains.
+    pub domain: Option<String>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            cookies_enabled: false,
+            secure: true,
+            same_site: SameSitePolicy::Strict,
+            domain: None,
+        }
+    }
+}
+
+/// Connections of `/ws`, which push events to authenticated clients.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct WebSocketConfig {
+    /// How often the server pings the client. Clients silent for two intervals are disconnected.
+    pub heartbeat_interval_secs: u64,
+    /// Time the client has to send the token, unless it's passed in the header.
+    pub auth_timeout_secs: u64,
+}
+
+impl WebSocketConfig {
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_interval_secs)
+    }
+
+    pub fn auth_timeout(&self) -> Duration {
+        Duration::from_secs(self.auth_timeout_secs)
+    }
+}
 
-        Ok(Jwt::new(encoding_key, decoding_key, jwk))
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval_secs: 30,
+            auth_timeout_secs: 10,
+        }
     }
 }
 
+/// OpenAPI specification, which is always served at `/openapi.json`.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct OpenApiConfig {
+    /// Serve documentation page rendered from the specification at `/docs`.
+    #[serde(default)]
+    pub docs_enabled: bool,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SameSitePolicy {
+    Strict,
+    Lax,
+    None,
+}
+
+fn timestamp(datetime: DateTime<Utc>) -> Result<TimeStamp> {
+    datetime
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+        .parse()
+        .wrap_err("Failed to convert datetime into `TimeStamp`")
+}
+
 pub fn load_config() -> Result<MainConfig> {
     let config_path = std::env::current_dir()
         .wrap_err("Failed to determine the current directory")?
@@ -122,3 +622,36 @@ pub enum Environment {
     Local,
     Production,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::AppConfig;
+
+    #[test]
+    fn trailing_slash_of_base_url_is_trimmed() {
+        let config: AppConfig = serde_json::from_value(json!({
+            "host": "127.0.0.1",
+            "port": 8000,
+            "base_url": "https://auth.battlemon.com/",
+        }))
+        .unwrap();
+
+        assert_eq!("https://auth.battlemon.com", config.base_url);
+    }
+
+    #[test]
+    fn drain_timeout_has_default() {
+        let config: AppConfig = serde_json::from_value(json!({
+            "host": "127.0.0.1",
+            "port": 8000,
+            "base_url": "https://auth.battlemon.com",
+        }))
+        .unwrap();
+
+        assert_eq!(Duration::from_secs(30), config.drain_timeout());
+    }
+}
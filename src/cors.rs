@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use eyre::{ensure, eyre, Result, WrapErr};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+
+use crate::config::CorsConfig;
+
+/// Origin allowed by CORS policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OriginPattern {
+    Exact(String),
+    /// Any subdomain of `domain`, e.g. `https://*.battlemon.com`.
+    Subdomain {
+        scheme: String,
+        domain: String,
+    },
+}
+
+impl OriginPattern {
+    fn parse(origin: &str) -> Result<Self> {
+        let (scheme, host) = origin
+            .split_once("://")
+            .filter(|(scheme, host)| !scheme.is_empty() && !host.is_empty())
+            .ok_or_else(|| eyre!("Origin `{origin}` doesn't have scheme and host"))?;
+        ensure!(
+            !host.contains('/'),
+            "Origin `{origin}` must not contain path"
+        );
+
+        match host.strip_prefix("*.") {
+            Some(domain) => {
+                ensure!(
+                    !domain.is_empty() && !domain.contains('*'),
+                    "Origin `{origin}` contains invalid wildcard"
+                );
+
+                Ok(Self::Subdomain {
+                    scheme: scheme.to_lowercase(),
+                    domain: domain.to_lowercase(),
+                })
+            }
+            None => {
+                ensure!(
+                    !host.contains('*'),
+                    "Origin `{origin}` contains invalid wildcard"
+                );
+
+                Ok(Self::Exact(origin.to_lowercase()))
+            }
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_lowercase();
+        match self {
+            Self::Exact(allowed) => origin == *allowed,
+            Self::Subdomain { scheme, domain } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|rest| rest.strip_prefix("://"))
+                .and_then(|host| host.strip_suffix(domain.as_str()))
+                .and_then(|subdomain| subdomain.strip_suffix('.'))
+                .is_some_and(|subdomain| {
+                    !subdomain.is_empty()
+                        && subdomain
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+                }),
+        }
+    }
+}
+
+/// Compose CORS layer of the configured policy.
+///
+/// Wildcards `*` can't be used along with credentials, browsers reject such responses.
+pub fn cors_layer(config: &CorsConfig) -> Result<CorsLayer> {
+    let wildcard = |values: &[String]| values.iter().any(|value| value == "*");
+    if config.allow_credentials {
+        ensure!(
+            !wildcard(&config.allowed_origins)
+                && !wildcard(&config.allowed_methods)
+                && !wildcard(&config.allowed_headers),
+            "Wildcard `*` isn't allowed along with credentials"
+        );
+    }
+
+    let allow_origin = if wildcard(&config.allowed_origins) {
+        AllowOrigin::any()
+    } else {
+        let patterns = config
+            .allowed_origins
+            .iter()
+            .map(|origin| OriginPattern::parse(origin))
+            .collect::<Result<Vec<_>>>()?;
+
+        AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| patterns.iter().any(|pattern| pattern.matches(origin)))
+        })
+    };
+    let allow_methods = if wildcard(&config.allowed_methods) {
+        AllowMethods::any()
+    } else {
+        config
+            .allowed_methods
+            .iter()
+            .map(|method| {
+                Method::from_bytes(method.to_uppercase().as_bytes())
+                    .wrap_err_with(|| format!("Failed to parse method `{method}`"))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into()
+    };
+    let allow_headers = if wildcard(&config.allowed_headers) {
+        AllowHeaders::any()
+    } else {
+        config
+            .allowed_headers
+            .iter()
+            .map(|header| {
+                header
+                    .parse::<HeaderName>()
+                    .wrap_err_with(|| format!("Failed to parse header `{header}`"))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into()
+    };
+
+    let layer = CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(allow_methods)
+        .allow_headers(allow_headers)
+        .allow_credentials(config.allow_credentials);
+
+    Ok(match config.max_age_secs {
+        Some(max_age_secs) => layer.max_age(Duration::from_secs(max_age_secs)),
+        None => layer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_origin_matches_itself_only() {
+        let pattern = OriginPattern::parse("https://battlemon.com").unwrap();
+
+        assert!(pattern.matches("https://battlemon.com"));
+        assert!(pattern.matches("HTTPS://Battlemon.com"));
+        assert!(!pattern.matches("http://battlemon.com"));
+        assert!(!pattern.matches("https://game.battlemon.com"));
+    }
+
+    #[test]
+    fn wildcard_origin_matches_subdomains() {
+        let pattern = OriginPattern::parse("https://*.battlemon.com").unwrap();
+
+        assert!(pattern.matches("https://game.battlemon.com"));
+        assert!(pattern.matches("https://eu.game.battlemon.com"));
+        assert!(!pattern.matches("https://battlemon.com"));
+        assert!(!pattern.matches("https://evilbattlemon.com"));
+        assert!(!pattern.matches("https://game.battlemon.com.evil.com"));
+        assert!(!pattern.matches("http://game.battlemon.com"));
+    }
+
+    #[test]
+    fn malformed_origins_are_rejected() {
+        assert!(OriginPattern::parse("battlemon.com").is_err());
+        assert!(OriginPattern::parse("https://battlemon.com/game").is_err());
+        assert!(OriginPattern::parse("https://game.*.battlemon.com").is_err());
+    }
+
+    #[test]
+    fn wildcard_is_rejected_with_credentials() {
+        let config = CorsConfig {
+            allowed_origins: vec!["*".to_owned()],
+            allow_credentials: true,
+            ..Default::default()
+        };
+
+        assert!(cors_layer(&config).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+use ethers::types::Address;
+use sqlx::PgPool;
+use thiserror::Error;
+use tracing::{instrument, warn};
+
+use crate::{
+    address::ToHex,
+    ethereum::{Ethereum, EthereumError},
+};
+
+/// Resolver of ENS names, which caches resolved names in database.
+///
+/// Without Ethereum node only cached names are available.
+#[derive(Clone)]
+pub struct EnsResolver {
+    ethereum: Option<Ethereum>,
+    db_pool: PgPool,
+    cache_ttl: chrono::Duration,
+}
+
+struct CachedName {
+    name: Option<String>,
+    resolved_at: DateTime<Utc>,
+}
+
+impl EnsResolver {
+    pub fn new(ethereum: Option<Ethereum>, db_pool: PgPool, cache_ttl: chrono::Duration) -> Self {
+        Self {
+            ethereum,
+            db_pool,
+            cache_ttl,
+        }
+    }
+
+    /// Primary ENS name of the address, refreshing the cached one if it's outdated.
+    ///
+    /// Failures of Ethereum node aren't fatal, the outdated name is returned instead.
+    #[instrument(name = "Get ENS name of address", skip(self))]
+    pub async fn name_of(&self, address: Address) -> Result<Option<String>, sqlx::Error> {
+        let user_id = address.to_hex();
+        let cached = get_cached_name_db(&user_id, &self.db_pool).await?;
+        let cached_name = match cached {
+            Some(cached) if cached.resolved_at + self.cache_ttl > Utc::now() => {
+                return Ok(cached.name)
+            }
+            Some(cached) => cached.name,
+            None => None,
+        };
+        let Some(ethereum) = &self.ethereum else {
+            return Ok(cached_name);
+        };
+
+        match ethereum.lookup_ens_name(address).await {
+            Ok(name) => {
+                upsert_cached_name_db(&user_id, name.as_deref(), &self.db_pool).await?;
+
+                Ok(name)
+            }
+            Err(e) => {
+                warn!("Failed to lookup ENS name of {user_id}: {e}");
+
+                Ok(cached_name)
+            }
+        }
+    }
+
+    /// Cached ENS name of the address regardless of its age.
+    #[instrument(name = "Get cached ENS name of address", skip(self))]
+    pub async fn cached_name_of(&self, user_id: &str) -> Result<Option<String>, sqlx::Error> {
+        let cached = get_cached_name_db(user_id, &self.db_pool).await?;
+
+        Ok(cached.and_then(|cached| cached.name))
+    }
+
+    /// Address the ENS name points to, preferring addresses whose primary name it is.
+    #[instrument(name = "Get address of ENS name", skip(self))]
+    pub async fn address_of(&self, name: &str) -> Result<Option<String>, EnsError> {
+        let cached = get_cached_address_db(name, &self.cache_ttl, &self.db_pool).await?;
+        if cached.is_some() {
+            return Ok(cached);
+        }
+        let Some(ethereum) = &self.ethereum else {
+            return Ok(None);
+        };
+
+        let address = ethereum.resolve_ens_name(name).await?;
+
+        Ok(address.map(|address| address.to_hex()))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum EnsError {
+    #[error("Failed to resolve ENS name: {0}")]
+    Ethereum(#[from] EthereumError),
+    #[error("Failed to get cached ENS name: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[instrument(name = "Get cached ENS name from database", skip(db_pool))]
+async fn get_cached_name_db(
+    user_id: &str,
+    db_pool: &PgPool,
+) -> Result<Option<CachedName>, sqlx::Error> {
+    sqlx::query_as!(
+        CachedName,
+        r#"
+        select name, resolved_at from ens_names
+        where user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await
+}
+
+#[instrument(name = "Get cached address of ENS name from database", skip(db_pool))]
+async fn get_cached_address_db(
+    name: &str,
+    cache_ttl: &chrono::Duration,
+    db_pool: &PgPool,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        select user_id from ens_names
+        where lower(name) = lower($1) and resolved_at > $2
+        order by resolved_at desc
+        limit 1
+        "#,
+        name,
+        Utc::now() - *cache_ttl
+    )
+    .fetch_optional(db_pool)
+    .await
+}
+
+#[instrument(name = "Store ENS name into database", skip(db_pool))]
+async fn upsert_cached_name_db(
+    user_id: &str,
+    name: Option<&str>,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        insert into ens_names(user_id, name, resolved_at)
+        values ($1, $2, now())
+        on conflict (user_id) do update set name = excluded.name, resolved_at = excluded.resolved_at
+        "#,
+        user_id,
+        name
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use ethers::{
+    prelude::abigen,
+    providers::{Http, Middleware, Provider, ProviderError},
+    types::{Address, Bytes, H256},
+};
+use eyre::{Result, WrapErr};
+use thiserror::Error;
+use tracing::instrument;
+
+use crate::config::EthereumConfig;
+
+abigen!(
+    Erc1271,
+    r#"[
+        function isValidSignature(bytes32 hash, bytes signature) external view returns (bytes4)
+    ]"#
+);
+
+/// Value returned by `isValidSignature` for valid signatures, see EIP-1271.
+pub const ERC1271_MAGIC_VALUE: [u8; 4] = [0x16, 0x26, 0xba, 0x7e];
+
+/// Client of Ethereum node, which bounds every request by the configured timeout.
+#[derive(Clone, Debug)]
+pub struct Ethereum {
+    provider: Arc<Provider<Http>>,
+    request_timeout: Duration,
+}
+
+impl Ethereum {
+    pub fn new(config: &EthereumConfig) -> Result<Self> {
+        let provider = Provider::<Http>::try_from(config.rpc_url.as_str())
+            .wrap_err("Failed to create provider for Ethereum node")?;
+
+        Ok(Self {
+            provider: Arc::new(provider),
+            request_timeout: config.request_timeout(),
+        })
+    }
+
+    pub fn provider(&self) -> Arc<Provider<Http>> {
+        self.provider.clone()
+    }
+
+    /// Ask the contract wallet at `address` whether `signature` of `hash` is valid, see EIP-1271.
+    ///
+    /// Returns `None` if there is no contract at `address`.
+    #[instrument(name = "Verify contract wallet signature", skip(self, signature))]
+    pub async fn is_valid_contract_signature(
+        &self,
+        address: Address,
+        hash: H256,
+        signature: Bytes,
+    ) -> Result<Option<bool>, EthereumError> {
+        self.with_timeout(async {
+            let code = self
+                .provider
+                .get_code(address, None)
+                .await
+                .map_err(|e| EthereumError::Request(e.to_string()))?;
+            if code.is_empty() {
+                return Ok(None);
+            }
+
+            let contract = Erc1271::new(address, self.provider.clone());
+            match contract
+                .is_valid_signature(hash.into(), signature)
+                .call()
+                .await
+            {
+                Ok(magic_value) => Ok(Some(magic_value == ERC1271_MAGIC_VALUE)),
+                // Some wallets revert instead of returning a non-magic value.
+                Err(e) if e.is_revert() => Ok(Some(false)),
+                Err(e) => Err(EthereumError::Request(e.to_string())),
+            }
+        })
+        .await
+    }
+
+    /// Primary ENS name of `address`, which is verified to resolve back to `address`.
+    #[instrument(name = "Lookup ENS name", skip(self))]
+    pub async fn lookup_ens_name(&self, address: Address) -> Result<Option<String>, EthereumError> {
+        self.with_timeout(async {
+            match self.provider.lookup_address(address).await {
+                Ok(name) => Ok(Some(name)),
+                Err(ProviderError::EnsError(_) | ProviderError::EnsNotOwned(_)) => Ok(None),
+                Err(e) => Err(EthereumError::Request(e.to_string())),
+            }
+        })
+        .await
+    }
+
+    /// Address the ENS name points to.
+    #[instrument(name = "Resolve ENS name", skip(self))]
+    pub async fn resolve_ens_name(&self, name: &str) -> Result<Option<Address>, EthereumError> {
+        self.with_timeout(async {
+            match self.provider.resolve_name(name).await {
+                Ok(address) if address.is_zero() => Ok(None),
+                Ok(address) => Ok(Some(address)),
+                Err(ProviderError::EnsError(_)) => Ok(None),
+                Err(e) => Err(EthereumError::Request(e.to_string())),
+            }
+        })
+        .await
+    }
+
+    /// Number of the latest block known to the node.
+    pub async fn block_number(&self) -> Result<u64, EthereumError> {
+        self.with_timeout(async {
+            self.provider
+                .get_block_number()
+                .await
+                .map(|block_number| block_number.as_u64())
+                .map_err(|e| EthereumError::Request(e.to_string()))
+        })
+        .await
+    }
+
+    pub async fn with_timeout<T>(
+        &self,
+        request: impl Future<Output = Result<T, EthereumError>>,
+    ) -> Result<T, EthereumError> {
+        tokio::time::timeout(self.request_timeout, request)
+            .await
+            .map_err(|_| EthereumError::Timeout)?
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum EthereumError {
+    #[error("Ethereum node didn't respond in time")]
+    Timeout,
+    #[error("Ethereum node request failed: {0}")]
+    Request(String),
+}
@@ -0,0 +1,149 @@
+use serde::Serialize;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::jwt::Claims;
+
+/// Number of events kept for subscribers, which haven't received them yet.
+///
+/// Subscribers lagging behind further miss the oldest events.
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+/// Event, which is pushed to the connected clients it concerns.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// Access token has been revoked, e.g. on logout.
+    SessionRevoked { account_id: Uuid, jti: Uuid },
+    /// Indexed NFT has changed its owner.
+    AssetTransferred {
+        contract: String,
+        token_id: String,
+        from: String,
+        to: String,
+        block_number: u64,
+    },
+    /// Profile of the account has been changed.
+    ProfileUpdated {
+        account_id: Uuid,
+        nickname: Option<String>,
+        avatar_url: Option<String>,
+    },
+}
+
+impl Event {
+    /// Whether the event concerns the holder of the token.
+    pub fn concerns(&self, claims: &Claims) -> bool {
+        match self {
+            Event::SessionRevoked { account_id, .. } | Event::ProfileUpdated { account_id, .. } => {
+                *account_id == claims.sub
+            }
+            Event::AssetTransferred { from, to, .. } => {
+                *from == claims.wallet || *to == claims.wallet
+            }
+        }
+    }
+}
+
+/// In-process bus, which fans out events to subscribers.
+///
+/// Events are delivered to subscribers of this instance only.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+
+        Self { sender }
+    }
+
+    /// Send the event to the current subscribers, if there are any.
+    pub fn publish(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALLET: &str = "0x4675c7e5baafbffbca748158becba61ef3b0a263";
+    const OTHER_WALLET: &str = "0x0000000000000000000000000000000000000001";
+
+    fn claims() -> Claims {
+        Claims {
+            iss: "http://localhost".to_owned(),
+            sub: Uuid::new_v4(),
+            exp: 0,
+            iat: 0,
+            jti: Uuid::new_v4(),
+            wallet: WALLET.to_owned(),
+            ens: None,
+            chain_id: 1,
+            roles: Vec::new(),
+            collections: Vec::new(),
+        }
+    }
+
+    fn transfer(from: &str, to: &str) -> Event {
+        Event::AssetTransferred {
+            contract: OTHER_WALLET.to_owned(),
+            token_id: "1".to_owned(),
+            from: from.to_owned(),
+            to: to.to_owned(),
+            block_number: 1,
+        }
+    }
+
+    #[test]
+    fn account_events_concern_the_account_only() {
+        let claims = claims();
+        let own = Event::ProfileUpdated {
+            account_id: claims.sub,
+            nickname: None,
+            avatar_url: None,
+        };
+        let foreign = Event::SessionRevoked {
+            account_id: Uuid::new_v4(),
+            jti: claims.jti,
+        };
+
+        assert!(own.concerns(&claims));
+        assert!(!foreign.concerns(&claims));
+    }
+
+    #[test]
+    fn transfers_concern_both_sides() {
+        let claims = claims();
+
+        assert!(transfer(WALLET, OTHER_WALLET).concerns(&claims));
+        assert!(transfer(OTHER_WALLET, WALLET).concerns(&claims));
+        assert!(!transfer(OTHER_WALLET, OTHER_WALLET).concerns(&claims));
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_published_events() {
+        let bus = EventBus::new();
+        let mut first = bus.subscribe();
+        let mut second = bus.subscribe();
+        let event = transfer(WALLET, OTHER_WALLET);
+
+        bus.publish(event.clone());
+
+        assert_eq!(event, first.recv().await.unwrap());
+        assert_eq!(event, second.recv().await.unwrap());
+    }
+}
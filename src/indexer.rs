@@ -0,0 +1,431 @@
+use ethers::{
+    providers::Middleware,
+    types::{Address, Filter, Log, H256, U256},
+};
+use eyre::{Result, WrapErr};
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::{task::JoinHandle, time::Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    address::ToHex,
+    config::{IndexedContractConfig, IndexerConfig},
+    ethereum::{Ethereum, EthereumError},
+    events::{Event, EventBus},
+};
+
+/// Signature of ERC-721 `Transfer` event.
+const TRANSFER_EVENT: &str = "Transfer(address,address,uint256)";
+
+/// Spawn the task, which follows `Transfer` events of the configured contracts.
+///
+/// Only blocks with enough confirmations are indexed. If a deeper reorg replaces the last
+/// indexed block anyway, transfers of the orphaned blocks are dropped and indexed again.
+///
+/// Indexed transfers are published to `events`.
+///
+/// The task stops after the range being indexed once `shutdown` is cancelled.
+pub fn spawn_indexer(
+    ethereum: Ethereum,
+    db_pool: PgPool,
+    config: IndexerConfig,
+    events: EventBus,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let indexer = Indexer {
+            ethereum,
+            db_pool,
+            events,
+            confirmations: config.confirmations,
+            max_block_range: config.max_block_range.max(1),
+        };
+        let poll_interval = config.poll_interval();
+        let mut interval = tokio::time::interval_at(Instant::now() + poll_interval, poll_interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            for contract in &config.contracts {
+                // Ranges are indexed in transactions, so it's safe to stop between them.
+                while !shutdown.is_cancelled() {
+                    match indexer.index_next_range(contract).await {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(e) => {
+                            error!(
+                                "Failed to index transfers of {}: {e:?}",
+                                contract.address.to_hex()
+                            );
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+struct Indexer {
+    ethereum: Ethereum,
+    db_pool: PgPool,
+    events: EventBus,
+    confirmations: u64,
+    max_block_range: u64,
+}
+
+impl Indexer {
+    /// Index transfers of the next range of confirmed blocks.
+    ///
+    /// Returns `true` if there are more confirmed blocks to index.
+    #[instrument(name = "Index transfers", skip(self), level = "debug")]
+    async fn index_next_range(&self, contract: &IndexedContractConfig) -> Result<bool> {
+        let address = contract.address.to_hex();
+        let confirmed = self
+            .ethereum
+            .block_number()
+            .await?
+            .saturating_sub(self.confirmations);
+        let checkpoint = get_checkpoint_db(&address, &self.db_pool)
+            .await
+            .wrap_err("Failed to get checkpoint")?;
+        let from = match checkpoint {
+            Some(checkpoint) => {
+                let block_number = checkpoint.block_number as u64;
+                if self.block_hash(block_number).await? != checkpoint.block_hash {
+                    self.rewind(contract, block_number).await?;
+
+                    return Ok(true);
+                }
+
+                block_number + 1
+            }
+            None => contract.start_block,
+        };
+        if from > confirmed {
+            return Ok(false);
+        }
+
+        let to = confirmed.min(from + self.max_block_range - 1);
+        let logs = self.transfer_logs(contract.address, from, to).await?;
+        let to_hash = self.block_hash(to).await?;
+        let transfers: Vec<_> = logs.iter().filter_map(Transfer::from_log).collect();
+
+        let mut tx = self
+            .db_pool
+            .begin()
+            .await
+            .wrap_err("Failed to start sql transaction")?;
+        for transfer in &transfers {
+            insert_transfer_db(&address, transfer, &mut tx)
+                .await
+                .wrap_err("Failed to store transfer")?;
+            upsert_owner_db(&address, transfer, &mut tx)
+                .await
+                .wrap_err("Failed to store owner")?;
+        }
+        upsert_checkpoint_db(&address, to, &to_hash, &mut tx)
+            .await
+            .wrap_err("Failed to store checkpoint")?;
+        tx.commit()
+            .await
+            .wrap_err("Failed to commit sql transaction")?;
+        for transfer in &transfers {
+            self.events.publish(Event::AssetTransferred {
+                contract: address.clone(),
+                token_id: transfer.token_id.to_string(),
+                from: transfer.from.to_hex(),
+                to: transfer.to.to_hex(),
+                block_number: transfer.block_number,
+            });
+        }
+        if !transfers.is_empty() {
+            info!(
+                "Indexed {} transfers of {address} in blocks {from}..={to}",
+                transfers.len()
+            );
+        }
+
+        Ok(to < confirmed)
+    }
+
+    /// Drop transfers of the blocks, which may have been orphaned along with `orphaned_block`.
+    #[instrument(name = "Rewind indexed transfers", skip(self))]
+    async fn rewind(&self, contract: &IndexedContractConfig, orphaned_block: u64) -> Result<()> {
+        let address = contract.address.to_hex();
+        let rewind_to = orphaned_block
+            .checked_sub(self.confirmations.max(1))
+            .filter(|block_number| *block_number >= contract.start_block);
+        warn!("Reorg of block {orphaned_block} detected, rewinding {address} to {rewind_to:?}");
+
+        let mut tx = self
+            .db_pool
+            .begin()
+            .await
+            .wrap_err("Failed to start sql transaction")?;
+        let last_kept_block = rewind_to.map_or(-1, |block_number| block_number as i64);
+        delete_transfers_after_db(&address, last_kept_block, &mut tx)
+            .await
+            .wrap_err("Failed to delete orphaned transfers")?;
+        match rewind_to {
+            Some(block_number) => {
+                let block_hash = self.block_hash(block_number).await?;
+                upsert_checkpoint_db(&address, block_number, &block_hash, &mut tx)
+                    .await
+                    .wrap_err("Failed to store checkpoint")?;
+            }
+            None => delete_checkpoint_db(&address, &mut tx)
+                .await
+                .wrap_err("Failed to delete checkpoint")?,
+        }
+        tx.commit()
+            .await
+            .wrap_err("Failed to commit sql transaction")?;
+
+        Ok(())
+    }
+
+    async fn block_hash(&self, block_number: u64) -> Result<String, EthereumError> {
+        self.ethereum
+            .with_timeout(async {
+                let block = self
+                    .ethereum
+                    .provider()
+                    .get_block(block_number)
+                    .await
+                    .map_err(|e| EthereumError::Request(e.to_string()))?;
+
+                block
+                    .and_then(|block| block.hash)
+                    .map(|hash| format!("{hash:#x}"))
+                    .ok_or_else(|| {
+                        EthereumError::Request(format!("Block {block_number} is absent"))
+                    })
+            })
+            .await
+    }
+
+    async fn transfer_logs(
+        &self,
+        contract: Address,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<Log>, EthereumError> {
+        let filter = Filter::new()
+            .address(contract)
+            .event(TRANSFER_EVENT)
+            .from_block(from)
+            .to_block(to);
+        self.ethereum
+            .with_timeout(async {
+                self.ethereum
+                    .provider()
+                    .get_logs(&filter)
+                    .await
+                    .map_err(|e| EthereumError::Request(e.to_string()))
+            })
+            .await
+    }
+}
+
+#[derive(Debug)]
+struct Transfer {
+    block_number: u64,
+    log_index: u64,
+    block_hash: H256,
+    token_id: U256,
+    from: Address,
+    to: Address,
+}
+
+impl Transfer {
+    fn from_log(log: &Log) -> Option<Self> {
+        if log.removed == Some(true) {
+            return None;
+        }
+        // ERC-20 transfers have the same signature, but their amount isn't indexed.
+        let [_, from, to, token_id] = log.topics.as_slice() else {
+            return None;
+        };
+
+        Some(Self {
+            block_number: log.block_number?.as_u64(),
+            log_index: log.log_index?.as_u64(),
+            block_hash: log.block_hash?,
+            token_id: U256::from_big_endian(token_id.as_bytes()),
+            from: Address::from(*from),
+            to: Address::from(*to),
+        })
+    }
+}
+
+struct Checkpoint {
+    block_number: i64,
+    block_hash: String,
+}
+
+#[instrument(
+    name = "Get indexer checkpoint from database",
+    skip(db_pool),
+    level = "debug"
+)]
+async fn get_checkpoint_db(
+    contract: &str,
+    db_pool: &PgPool,
+) -> Result<Option<Checkpoint>, sqlx::Error> {
+    sqlx::query_as!(
+        Checkpoint,
+        r#"
+        select block_number, block_hash from indexer_checkpoints
+        where contract = $1
+        "#,
+        contract
+    )
+    .fetch_optional(db_pool)
+    .await
+}
+
+#[instrument(
+    name = "Store indexer checkpoint into database",
+    skip(tx),
+    level = "debug"
+)]
+async fn upsert_checkpoint_db(
+    contract: &str,
+    block_number: u64,
+    block_hash: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        insert into indexer_checkpoints(contract, block_number, block_hash)
+        values ($1, $2, $3)
+        on conflict (contract) do update
+        set block_number = excluded.block_number, block_hash = excluded.block_hash
+        "#,
+        contract,
+        block_number as i64,
+        block_hash
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Delete indexer checkpoint from database", skip(tx))]
+async fn delete_checkpoint_db(
+    contract: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        delete from indexer_checkpoints
+        where contract = $1
+        "#,
+        contract
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Store transfer into database", skip(tx), level = "debug")]
+async fn insert_transfer_db(
+    contract: &str,
+    transfer: &Transfer,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        insert into token_transfers(contract, block_number, log_index, block_hash, token_id, from_address, to_address)
+        values ($1, $2, $3, $4, $5, $6, $7)
+        on conflict do nothing
+        "#,
+        contract,
+        transfer.block_number as i64,
+        transfer.log_index as i64,
+        format!("{:#x}", transfer.block_hash),
+        transfer.token_id.to_string(),
+        transfer.from.to_hex(),
+        transfer.to.to_hex()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Make the recipient of the transfer the owner, unless a later transfer is already indexed.
+#[instrument(name = "Store token owner into database", skip(tx), level = "debug")]
+async fn upsert_owner_db(
+    contract: &str,
+    transfer: &Transfer,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        insert into token_owners(contract, token_id, owner, block_number, log_index)
+        values ($1, $2, $3, $4, $5)
+        on conflict (contract, token_id) do update
+        set owner = excluded.owner, block_number = excluded.block_number, log_index = excluded.log_index
+        where (token_owners.block_number, token_owners.log_index) < (excluded.block_number, excluded.log_index)
+        "#,
+        contract,
+        transfer.token_id.to_string(),
+        transfer.to.to_hex(),
+        transfer.block_number as i64,
+        transfer.log_index as i64
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete transfers after `block_number` and restore owners from the remaining ones.
+#[instrument(name = "Delete orphaned transfers from database", skip(tx))]
+async fn delete_transfers_after_db(
+    contract: &str,
+    block_number: i64,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        delete from token_transfers
+        where contract = $1 and block_number > $2
+        "#,
+        contract,
+        block_number
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        delete from token_owners
+        where contract = $1 and block_number > $2
+        "#,
+        contract,
+        block_number
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        insert into token_owners(contract, token_id, owner, block_number, log_index)
+        select distinct on (token_id) contract, token_id, to_address, block_number, log_index
+        from token_transfers
+        where contract = $1
+        order by token_id, block_number desc, log_index desc
+        on conflict do nothing
+        "#,
+        contract
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
@@ -1,61 +1,296 @@
-use chrono::{Duration, Utc};
-use eyre::{Result, WrapErr};
-use jsonwebtoken::{jwk::Jwk, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use eyre::{ensure, eyre, Result, WrapErr};
+use jsonwebtoken::{
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use uuid::Uuid;
 
+use crate::role::Role;
+
+/// Lifetime of access tokens.
+pub const ACCESS_TOKEN_TTL_SECS: i64 = 3600;
+
+/// Key pair, which is used to sign and verify tokens.
+#[derive(Clone)]
+pub struct JwtKey {
+    /// Identifier of the key, which is placed into `kid` header of the token.
+    pub id: String,
+    pub encoding_key: EncodingKey,
+    pub decoding_key: DecodingKey,
+    pub jwk: Jwk,
+}
+
+/// Key ring, which signs tokens with one designated key and verifies them with any known key.
 #[derive(Clone)]
 pub struct Jwt {
-    encoding_key: EncodingKey,
-    decoding_key: DecodingKey,
-    jwk: Jwk,
+    keys: Arc<HashMap<String, JwtKey>>,
+    signing_key_id: String,
+    jwk_set: Arc<JwkSet>,
+    issuer: String,
 }
 
 impl Jwt {
-    pub fn new(encoding_key: EncodingKey, decoding_key: DecodingKey, jwk: Jwk) -> Self {
-        Self {
-            encoding_key,
-            decoding_key,
-            jwk,
-        }
+    pub fn new(keys: Vec<JwtKey>, signing_key_id: &str, issuer: String) -> Result<Self> {
+        let jwk_set = JwkSet {
+            keys: keys.iter().map(|key| key.jwk.clone()).collect(),
+        };
+        let keys: HashMap<_, _> = keys.into_iter().map(|key| (key.id.clone(), key)).collect();
+        ensure!(
+            keys.contains_key(signing_key_id),
+            "Signing key `{signing_key_id}` is absent in the key ring"
+        );
+
+        Ok(Self {
+            keys: Arc::new(keys),
+            signing_key_id: signing_key_id.to_owned(),
+            jwk_set: Arc::new(jwk_set),
+            issuer,
+        })
     }
 
-    pub fn encode(&self, user_id: String) -> Result<String> {
+    /// Encode the token of the subject, returning it along with its claims.
+    pub fn encode(&self, subject: Subject) -> Result<(String, Claims)> {
         let now = Utc::now();
-        let expires_at = now + Duration::hours(1);
+        let expires_at = now + Duration::seconds(ACCESS_TOKEN_TTL_SECS);
         let claims = Claims {
-            sub: user_id,
+            iss: self.issuer.clone(),
+            sub: subject.account_id,
             exp: expires_at.timestamp(),
             iat: now.timestamp(),
+            jti: Uuid::new_v4(),
+            wallet: subject.wallet,
+            ens: subject.ens,
+            chain_id: subject.chain_id,
+            roles: subject.roles,
+            collections: subject.collections,
         };
+        let signing_key = self.signing_key();
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(signing_key.id.clone());
 
-        jsonwebtoken::encode(&Header::new(Algorithm::EdDSA), &claims, &self.encoding_key)
-            .wrap_err("Failed to encode claims")
+        let token = jsonwebtoken::encode(&header, &claims, &signing_key.encoding_key)
+            .wrap_err("Failed to encode claims")?;
+
+        Ok((token, claims))
     }
 
     pub fn decode(&self, token: &str) -> Result<Claims> {
-        jsonwebtoken::decode(
-            token,
-            &self.decoding_key,
-            &Validation::new(Algorithm::EdDSA),
-        )
-        .map(|decoded| decoded.claims)
-        .wrap_err("Failed to decode token")
+        let header = jsonwebtoken::decode_header(token).wrap_err("Failed to decode header")?;
+        let kid = header
+            .kid
+            .ok_or_else(|| eyre!("Token doesn't contain key id"))?;
+        let key = self
+            .keys
+            .get(&kid)
+            .ok_or_else(|| eyre!("Unknown key id `{kid}`"))?;
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.set_issuer(&[&self.issuer]);
+
+        jsonwebtoken::decode(token, &key.decoding_key, &validation)
+            .map(|decoded| decoded.claims)
+            .wrap_err("Failed to decode token")
     }
 
+    /// Public key of the signing key.
     pub fn jwk(&self) -> &Jwk {
-        &self.jwk
+        &self.signing_key().jwk
+    }
+
+    /// Public keys, which can be used to verify issued tokens.
+    pub fn jwk_set(&self) -> &JwkSet {
+        &self.jwk_set
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    fn signing_key(&self) -> &JwtKey {
+        &self.keys[&self.signing_key_id]
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Account the token is issued for.
+#[derive(Debug, Clone)]
+pub struct Subject {
+    pub account_id: Uuid,
+    /// Address of the wallet the user has signed in with.
+    pub wallet: String,
+    /// Primary ENS name of the wallet.
+    pub ens: Option<String>,
+    pub chain_id: u64,
+    pub roles: Vec<Role>,
+    /// Names of configured NFT collections held by the wallets of the account.
+    pub collections: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
-    pub sub: String,
+    pub iss: String,
+    /// Id of the account, which stays the same for all linked wallets.
+    pub sub: Uuid,
     pub exp: i64,
     pub iat: i64,
+    /// Unique id of the token, which is used to revoke it.
+    pub jti: Uuid,
+    /// Address of the wallet the user has signed in with.
+    pub wallet: String,
+    /// Primary ENS name of the wallet at the moment of issuing the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ens: Option<String>,
+    /// EIP-155 chain the user has signed in on.
+    pub chain_id: u64,
+    /// Roles of the user at the moment of issuing the token.
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    /// NFT collections held by the wallets of the account at the moment of issuing the token.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collections: Vec<String>,
 }
 
 impl Claims {
     pub fn expired(&self) -> bool {
         Utc::now().timestamp() > self.exp
     }
+
+    /// Admin passes any role requirement.
+    pub fn has_role(&self, role: Role) -> bool {
+        self.roles.contains(&role) || self.roles.contains(&Role::Admin)
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.exp, 0)
+            .single()
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use ring::{rand::SystemRandom, signature::Ed25519KeyPair};
+    use secrecy::Secret;
+
+    use crate::config::SecretsConfig;
+
+    use super::*;
+
+    const ISSUER: &str = "http://localhost";
+
+    fn generate_key_pair() -> Secret<String> {
+        let document = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+
+        Secret::new(base64::engine::general_purpose::STANDARD.encode(document.as_ref()))
+    }
+
+    fn subject(roles: Vec<Role>) -> Subject {
+        Subject {
+            account_id: Uuid::nil(),
+            wallet: "0x0000000000000000000000000000000000000000".to_owned(),
+            ens: None,
+            chain_id: 1,
+            roles,
+            collections: Vec::new(),
+        }
+    }
+
+    fn jwt(signing_key_id: &str, key_pairs: &[(&str, &Secret<String>)]) -> Result<Jwt> {
+        SecretsConfig {
+            signing_key_id: signing_key_id.to_owned(),
+            key_pairs: key_pairs
+                .iter()
+                .map(|(id, key_pair)| (id.to_string(), (*key_pair).clone()))
+                .collect(),
+        }
+        .jwt(ISSUER)
+    }
+
+    #[test]
+    fn token_header_contains_signing_key_id() {
+        let old_key = generate_key_pair();
+        let new_key = generate_key_pair();
+        let jwt = jwt("new", &[("old", &old_key), ("new", &new_key)]).unwrap();
+
+        let (token, _) = jwt.encode(subject(vec![Role::Player])).unwrap();
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+
+        assert_eq!(Some("new".to_owned()), header.kid);
+    }
+
+    #[test]
+    fn tokens_signed_by_previous_key_are_valid_after_rotation() {
+        let old_key = generate_key_pair();
+        let new_key = generate_key_pair();
+        let before_rotation = jwt("old", &[("old", &old_key)]).unwrap();
+        let after_rotation = jwt("new", &[("old", &old_key), ("new", &new_key)]).unwrap();
+
+        let (token, _) = before_rotation.encode(subject(vec![Role::Player])).unwrap();
+        let claims = after_rotation.decode(&token).unwrap();
+
+        assert_eq!(Uuid::nil(), claims.sub);
+    }
+
+    #[test]
+    fn tokens_signed_by_retired_key_are_rejected() {
+        let old_key = generate_key_pair();
+        let new_key = generate_key_pair();
+        let before_rotation = jwt("old", &[("old", &old_key)]).unwrap();
+        let after_retirement = jwt("new", &[("new", &new_key)]).unwrap();
+
+        let (token, _) = before_rotation.encode(subject(vec![Role::Player])).unwrap();
+
+        assert!(after_retirement.decode(&token).is_err());
+    }
+
+    #[test]
+    fn jwk_set_contains_all_keys() {
+        let old_key = generate_key_pair();
+        let new_key = generate_key_pair();
+        let jwt = jwt("new", &[("old", &old_key), ("new", &new_key)]).unwrap();
+
+        let mut kids: Vec<_> = jwt
+            .jwk_set()
+            .keys
+            .iter()
+            .map(|jwk| jwk.common.key_id.clone().unwrap())
+            .collect();
+        kids.sort();
+
+        assert_eq!(vec!["new", "old"], kids);
+    }
+
+    #[test]
+    fn roles_are_emitted_in_claims() {
+        let key = generate_key_pair();
+        let jwt = jwt("key", &[("key", &key)]).unwrap();
+
+        let (token, _) = jwt
+            .encode(subject(vec![Role::Player, Role::Moderator]))
+            .unwrap();
+        let claims = jwt.decode(&token).unwrap();
+
+        assert!(claims.has_role(Role::Moderator));
+        assert!(!claims.has_role(Role::Service));
+    }
+
+    #[test]
+    fn admin_passes_any_role_requirement() {
+        let key = generate_key_pair();
+        let jwt = jwt("key", &[("key", &key)]).unwrap();
+
+        let (token, _) = jwt.encode(subject(vec![Role::Admin])).unwrap();
+        let claims = jwt.decode(&token).unwrap();
+
+        assert!(claims.has_role(Role::Service));
+    }
+
+    #[test]
+    fn absent_signing_key_is_rejected() {
+        let key = generate_key_pair();
+
+        assert!(jwt("absent", &[("present", &key)]).is_err());
+    }
 }
@@ -1,6 +1,18 @@
 pub mod address;
 pub mod config;
+pub mod cors;
+pub mod ens;
+pub mod ethereum;
+pub mod events;
+pub mod indexer;
 pub mod jwt;
+pub mod metrics;
+pub mod nft;
+pub mod nonce;
+pub mod rate_limit;
+pub mod refresh_token;
+pub mod revocation;
+pub mod role;
 pub mod routes;
 pub mod startup;
 pub mod telemetry;
@@ -4,16 +4,25 @@ use tracing::info;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let config = config::load_config().wrap_err("Failed to load app config")?;
+    let tracer = config
+        .otlp
+        .as_ref()
+        .map(telemetry::otlp_tracer)
+        .transpose()
+        .wrap_err("Failed to setup OTLP tracer")?;
     let subscriber = telemetry::build_subscriber(
         env!("CARGO_CRATE_NAME").into(),
         "info".into(),
         std::io::stdout,
+        tracer,
     );
     telemetry::init_subscriber(subscriber).wrap_err("Failed to init tracing subscriber")?;
-    info!("Loading application config");
-    let config = config::load_config().wrap_err("Failed to load app config")?;
+    info!("Loaded application config");
     let app = App::build(config).await?;
-    app.run_until_stopped().await?;
+    let result = app.run_until_stopped().await;
+    // Export spans, which are still buffered.
+    opentelemetry::global::shutdown_tracer_provider();
 
-    Ok(())
+    result
 }
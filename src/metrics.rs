@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use eyre::{Result, WrapErr};
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::PgPool;
+
+/// Prometheus metrics of the service.
+///
+/// Every instance has its own registry, so apps spawned in the same process don't share counters.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    logins: IntCounterVec,
+    nonces_issued: IntCounter,
+    jwt_verification_failures: IntCounterVec,
+    db_pool_connections: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new(max_db_connections: u32) -> Result<Self> {
+        let registry = Registry::new();
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of handled HTTP requests"),
+            &["method", "route", "status"],
+        )?;
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Latency of handled HTTP requests",
+            ),
+            &["method", "route", "status"],
+        )?;
+        let logins = IntCounterVec::new(
+            Opts::new("logins_total", "Number of sign-in attempts"),
+            &["outcome", "reason"],
+        )?;
+        let nonces_issued = IntCounter::new("nonces_issued_total", "Number of issued nonces")?;
+        let jwt_verification_failures = IntCounterVec::new(
+            Opts::new(
+                "jwt_verification_failures_total",
+                "Number of rejected access tokens",
+            ),
+            &["reason"],
+        )?;
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new(
+                "db_pool_connections",
+                "Number of database connections by state",
+            ),
+            &["state"],
+        )?;
+        let db_pool_max_connections = IntGauge::new(
+            "db_pool_max_connections",
+            "Maximal number of database connections",
+        )?;
+        db_pool_max_connections.set(i64::from(max_db_connections));
+
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_request_duration.clone()))?;
+        registry.register(Box::new(logins.clone()))?;
+        registry.register(Box::new(nonces_issued.clone()))?;
+        registry.register(Box::new(jwt_verification_failures.clone()))?;
+        registry.register(Box::new(db_pool_connections.clone()))?;
+        registry.register(Box::new(db_pool_max_connections))?;
+
+        Ok(Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            logins,
+            nonces_issued,
+            jwt_verification_failures,
+            db_pool_connections,
+        })
+    }
+
+    /// Count the request to the route `route` and observe its latency.
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let status = status.to_string();
+        let labels = [method, route, status.as_str()];
+        self.http_requests.with_label_values(&labels).inc();
+        self.http_request_duration
+            .with_label_values(&labels)
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Count the sign-in attempt, which has failed with `reason` if it's set.
+    pub fn observe_login(&self, failure_reason: Option<&str>) {
+        let labels = match failure_reason {
+            None => ["success", ""],
+            Some(reason) => ["failure", reason],
+        };
+        self.logins.with_label_values(&labels).inc();
+    }
+
+    pub fn observe_nonce_issued(&self) {
+        self.nonces_issued.inc();
+    }
+
+    pub fn observe_jwt_verification_failure(&self, reason: &str) {
+        self.jwt_verification_failures
+            .with_label_values(&[reason])
+            .inc();
+    }
+
+    /// Encode all metrics in Prometheus text format, sampling the state of `db_pool` beforehand.
+    pub fn render(&self, db_pool: &PgPool) -> Result<String> {
+        let size = i64::from(db_pool.size());
+        let idle = db_pool.num_idle() as i64;
+        self.db_pool_connections
+            .with_label_values(&["idle"])
+            .set(idle);
+        self.db_pool_connections
+            .with_label_values(&["in_use"])
+            .set(size - idle);
+
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .wrap_err("Failed to encode metrics")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_counted_per_route_and_status() {
+        let metrics = Metrics::new(10).unwrap();
+
+        metrics.observe_request("GET", "/me", 200, Duration::from_millis(5));
+        metrics.observe_request("GET", "/me", 200, Duration::from_millis(7));
+        metrics.observe_request("GET", "/me", 401, Duration::from_millis(1));
+
+        let ok = metrics
+            .http_requests
+            .with_label_values(&["GET", "/me", "200"]);
+        let unauthorized = metrics
+            .http_requests
+            .with_label_values(&["GET", "/me", "401"]);
+        assert_eq!(2, ok.get());
+        assert_eq!(1, unauthorized.get());
+        let latency = metrics
+            .http_request_duration
+            .with_label_values(&["GET", "/me", "200"]);
+        assert_eq!(2, latency.get_sample_count());
+    }
+
+    #[test]
+    fn logins_are_counted_by_outcome_and_reason() {
+        let metrics = Metrics::new(10).unwrap();
+
+        metrics.observe_login(None);
+        metrics.observe_login(Some("expired_nonce"));
+        metrics.observe_login(Some("expired_nonce"));
+
+        assert_eq!(1, metrics.logins.with_label_values(&["success", ""]).get());
+        let expired = metrics
+            .logins
+            .with_label_values(&["failure", "expired_nonce"]);
+        assert_eq!(2, expired.get());
+    }
+}
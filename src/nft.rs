@@ -0,0 +1,225 @@
+use std::sync::Arc;
+
+use ethers::{
+    prelude::abigen,
+    types::{Address, U256},
+};
+use eyre::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{CollectionConfig, NftConfig, TokenStandard},
+    ethereum::{Ethereum, EthereumError},
+};
+
+abigen!(
+    Erc721,
+    r#"[
+        function balanceOf(address owner) external view returns (uint256)
+        function tokenOfOwnerByIndex(address owner, uint256 index) external view returns (uint256)
+    ]"#
+);
+
+abigen!(
+    Erc1155,
+    r#"[
+        function balanceOfBatch(address[] accounts, uint256[] ids) external view returns (uint256[])
+    ]"#
+);
+
+/// Upper bound of token ids listed per wallet and ERC-721 collection.
+const MAX_LISTED_TOKENS: u64 = 100;
+
+/// Tokens of the collection owned by the wallets of the account.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct CollectionHoldings {
+    pub collection: String,
+    pub contract: String,
+    pub standard: TokenStandard,
+    /// Number of owned tokens as a decimal string.
+    pub balance: String,
+    /// Ids of owned tokens as decimal strings, empty if the contract can't list them.
+    pub token_ids: Vec<String>,
+}
+
+/// Inspector of NFTs owned by wallets, which queries configured collections via Ethereum node.
+#[derive(Clone)]
+pub struct NftInspector {
+    ethereum: Option<Ethereum>,
+    config: Arc<NftConfig>,
+}
+
+impl NftInspector {
+    pub fn new(config: NftConfig, ethereum: Option<Ethereum>) -> Result<Self> {
+        ensure!(
+            config.collections.is_empty() || ethereum.is_some(),
+            "NFT collections require Ethereum node to be configured"
+        );
+        let required = config
+            .login_requires
+            .iter()
+            .chain(config.route_requirements.values().flatten());
+        for name in required {
+            ensure!(
+                config
+                    .collections
+                    .iter()
+                    .any(|collection| &collection.name == name),
+                "Required collection `{name}` isn't configured"
+            );
+        }
+
+        Ok(Self {
+            ethereum,
+            config: Arc::new(config),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.config.collections.is_empty()
+    }
+
+    pub fn login_gated(&self) -> bool {
+        !self.config.login_requires.is_empty()
+    }
+
+    pub fn login_allowed(&self, held: &[String]) -> bool {
+        requirements_met(&self.config.login_requires, held)
+    }
+
+    /// Collections, at least one of which is required to access the route.
+    pub fn route_requirements(&self, path: &str) -> Option<&[String]> {
+        self.config.route_requirements.get(path).map(Vec::as_slice)
+    }
+
+    /// Tokens of every configured collection owned by any of `owners`.
+    #[instrument(name = "Get NFT holdings", skip(self))]
+    pub async fn holdings_of(
+        &self,
+        owners: &[Address],
+    ) -> Result<Vec<CollectionHoldings>, EthereumError> {
+        let Some(ethereum) = &self.ethereum else {
+            return Ok(Vec::new());
+        };
+
+        let mut holdings = Vec::with_capacity(self.config.collections.len());
+        for collection in &self.config.collections {
+            let (balance, token_ids) = match collection.standard {
+                TokenStandard::Erc721 => erc721_holdings(ethereum, collection, owners).await?,
+                TokenStandard::Erc1155 => erc1155_holdings(ethereum, collection, owners).await?,
+            };
+            holdings.push(CollectionHoldings {
+                collection: collection.name.clone(),
+                contract: format!("{:#x}", collection.address),
+                standard: collection.standard,
+                balance: balance.to_string(),
+                token_ids: token_ids.iter().map(ToString::to_string).collect(),
+            });
+        }
+
+        Ok(holdings)
+    }
+}
+
+/// Names of collections, in which at least one token is owned.
+pub fn held_collections(holdings: &[CollectionHoldings]) -> Vec<String> {
+    holdings
+        .iter()
+        .filter(|holdings| holdings.balance != "0")
+        .map(|holdings| holdings.collection.clone())
+        .collect()
+}
+
+/// Empty requirements are met by anyone, otherwise at least one collection has to be held.
+pub fn requirements_met(required: &[String], held: &[String]) -> bool {
+    required.is_empty() || required.iter().any(|collection| held.contains(collection))
+}
+
+async fn erc721_holdings(
+    ethereum: &Ethereum,
+    collection: &CollectionConfig,
+    owners: &[Address],
+) -> Result<(U256, Vec<U256>), EthereumError> {
+    let contract = Erc721::new(collection.address, ethereum.provider());
+    ethereum
+        .with_timeout(async {
+            let mut total = U256::zero();
+            let mut token_ids = Vec::new();
+            for owner in owners {
+                let balance = contract
+                    .balance_of(*owner)
+                    .call()
+                    .await
+                    .map_err(|e| EthereumError::Request(e.to_string()))?;
+                total += balance;
+                if !collection.enumerable {
+                    continue;
+                }
+
+                for index in 0..balance.min(MAX_LISTED_TOKENS.into()).as_u64() {
+                    let token_id = contract
+                        .token_of_owner_by_index(*owner, index.into())
+                        .call()
+                        .await
+                        .map_err(|e| EthereumError::Request(e.to_string()))?;
+                    token_ids.push(token_id);
+                }
+            }
+
+            Ok((total, token_ids))
+        })
+        .await
+}
+
+async fn erc1155_holdings(
+    ethereum: &Ethereum,
+    collection: &CollectionConfig,
+    owners: &[Address],
+) -> Result<(U256, Vec<U256>), EthereumError> {
+    let contract = Erc1155::new(collection.address, ethereum.provider());
+    let ids: Vec<U256> = collection.token_ids.iter().map(|&id| id.into()).collect();
+    ethereum
+        .with_timeout(async {
+            let mut total = U256::zero();
+            let mut token_ids = Vec::new();
+            for owner in owners {
+                let balances = contract
+                    .balance_of_batch(vec![*owner; ids.len()], ids.clone())
+                    .call()
+                    .await
+                    .map_err(|e| EthereumError::Request(e.to_string()))?;
+                for (id, balance) in ids.iter().zip(balances) {
+                    if balance.is_zero() {
+                        continue;
+                    }
+                    total += balance;
+                    if !token_ids.contains(id) {
+                        token_ids.push(*id);
+                    }
+                }
+            }
+
+            Ok((total, token_ids))
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_requirements_are_met_by_anyone() {
+        assert!(requirements_met(&[], &[]));
+    }
+
+    #[test]
+    fn any_of_required_collections_is_enough() {
+        let required = ["lemons".to_owned(), "outfits".to_owned()];
+
+        assert!(requirements_met(&required, &["outfits".to_owned()]));
+        assert!(!requirements_met(&required, &["weapons".to_owned()]));
+    }
+}
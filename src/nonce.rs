@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+
+use crate::revocation::{delete_expired_issued_tokens_db, delete_expired_revocations_db};
+
+/// Single-use value that user embeds into the signed challenge.
+#[derive(Debug, Clone)]
+pub struct Nonce {
+    pub value: Uuid,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// EIP-155 chain the sign-in request is bound to.
+    pub chain_id: u64,
+    pub purpose: NoncePurpose,
+}
+
+impl Nonce {
+    pub fn new(ttl: chrono::Duration, chain_id: u64, purpose: NoncePurpose) -> Self {
+        let issued_at = Utc::now();
+
+        Self {
+            value: Uuid::new_v4(),
+            issued_at,
+            expires_at: issued_at + ttl,
+            chain_id,
+            purpose,
+        }
+    }
+}
+
+/// Action the signature of the challenge authorizes, so it can't be replayed for another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoncePurpose {
+    SignIn,
+    /// Link the wallet to the account.
+    LinkWallet {
+        account_id: Uuid,
+    },
+}
+
+impl NoncePurpose {
+    const SIGN_IN: &'static str = "sign_in";
+    const LINK_WALLET: &'static str = "link_wallet";
+
+    /// Parse the purpose stored along with the nonce.
+    pub fn from_db(purpose: &str, account_id: Option<Uuid>) -> Option<Self> {
+        match (purpose, account_id) {
+            (Self::SIGN_IN, None) => Some(Self::SignIn),
+            (Self::LINK_WALLET, Some(account_id)) => Some(Self::LinkWallet { account_id }),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SignIn => Self::SIGN_IN,
+            Self::LinkWallet { .. } => Self::LINK_WALLET,
+        }
+    }
+
+    pub fn account_id(&self) -> Option<Uuid> {
+        match self {
+            Self::SignIn => None,
+            Self::LinkWallet { account_id } => Some(*account_id),
+        }
+    }
+}
+
+/// Spawn task, which periodically removes expired nonces, along with issued tokens and
+/// revocations of tokens, which have expired, from database until `shutdown`.
+pub fn spawn_nonce_sweeper(
+    db_pool: PgPool,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            match delete_expired_nonces_db(&db_pool).await {
+                Ok(0) => {}
+                Ok(deleted) => info!("Deleted {deleted} expired nonces"),
+                Err(e) => error!("Failed to delete expired nonces: {e}"),
+            }
+            match delete_expired_revocations_db(&db_pool).await {
+                Ok(0) => {}
+                Ok(deleted) => info!("Deleted {deleted} revocations of expired tokens"),
+                Err(e) => error!("Failed to delete revocations of expired tokens: {e}"),
+            }
+            match delete_expired_issued_tokens_db(&db_pool).await {
+                Ok(0) => {}
+                Ok(deleted) => info!("Deleted {deleted} expired issued tokens"),
+                Err(e) => error!("Failed to delete expired issued tokens: {e}"),
+            }
+        }
+    })
+}
+
+#[instrument(
+    name = "Delete expired nonces from database",
+    skip_all,
+    level = "debug"
+)]
+pub async fn delete_expired_nonces_db(db_pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let ret = sqlx::query!(
+        r#"
+        delete from nonces where expires_at < now()
+        "#
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(ret.rows_affected())
+}
@@ -0,0 +1,138 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::config::{RateLimitConfig, RouteRateLimit, TokenBucketConfig};
+
+/// Upper bound of tracked buckets, after reaching it full buckets are evicted.
+const MAX_TRACKED_BUCKETS: usize = 100_000;
+
+/// Rate limiter, which keeps a token bucket per route and client IP or target address.
+///
+/// Buckets live in memory, so every instance of the service limits requests on its own.
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: Arc<RateLimitConfig>,
+    buckets: Arc<Mutex<HashMap<BucketKey, Bucket>>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct BucketKey {
+    route: String,
+    key: String,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    /// Moment the bucket gets full again and can be forgotten.
+    full_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            buckets: Default::default(),
+        }
+    }
+
+    pub fn trust_forwarded_for(&self) -> bool {
+        self.config.trust_forwarded_for
+    }
+
+    pub fn route_limits(&self, route: &str) -> Option<&RouteRateLimit> {
+        self.config.routes.get(route)
+    }
+
+    /// Take a token from the bucket of `key` on `route`.
+    ///
+    /// Returns the time until a token is available, if the bucket is empty.
+    pub fn acquire(
+        &self,
+        route: &str,
+        key: &str,
+        limit: &TokenBucketConfig,
+    ) -> Result<(), Duration> {
+        let now = Instant::now();
+        let capacity = f64::from(limit.burst);
+        let per_second = f64::from(limit.per_minute.max(1)) / 60.0;
+        let key = BucketKey {
+            route: route.to_owned(),
+            key: key.to_owned(),
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_TRACKED_BUCKETS && !buckets.contains_key(&key) {
+            buckets.retain(|_, bucket| bucket.full_at > now);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+            full_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.updated_at = now;
+        if bucket.tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second));
+        }
+
+        bucket.tokens -= 1.0;
+        bucket.full_at = now + Duration::from_secs_f64((capacity - bucket.tokens) / per_second);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: TokenBucketConfig = TokenBucketConfig {
+        burst: 2,
+        per_minute: 60,
+    };
+
+    #[test]
+    fn burst_is_allowed_and_then_limited() {
+        let limiter = RateLimiter::new(RateLimitConfig::default());
+
+        assert!(limiter
+            .acquire("/web3_auth", "ip:127.0.0.1", &LIMIT)
+            .is_ok());
+        assert!(limiter
+            .acquire("/web3_auth", "ip:127.0.0.1", &LIMIT)
+            .is_ok());
+        let retry_after = limiter
+            .acquire("/web3_auth", "ip:127.0.0.1", &LIMIT)
+            .unwrap_err();
+
+        assert!(retry_after <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn buckets_are_separate_per_route_and_key() {
+        let limiter = RateLimiter::new(RateLimitConfig::default());
+        let limit = TokenBucketConfig {
+            burst: 1,
+            per_minute: 1,
+        };
+
+        assert!(limiter
+            .acquire("/web3_auth", "ip:127.0.0.1", &limit)
+            .is_ok());
+        assert!(limiter
+            .acquire("/web3_auth", "ip:127.0.0.2", &limit)
+            .is_ok());
+        assert!(limiter
+            .acquire("/users/:user_id/nonce", "ip:127.0.0.1", &limit)
+            .is_ok());
+        assert!(limiter
+            .acquire("/web3_auth", "ip:127.0.0.1", &limit)
+            .is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use eyre::{eyre, Result};
+use ring::{
+    digest::{digest, SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use uuid::Uuid;
+
+const TOKEN_LENGTH: usize = 32;
+
+/// Opaque long-lived token, which can be exchanged for a new access token.
+///
+/// Only the hash of the token is stored in database, so leaked rows can't be used to
+/// obtain access tokens. All tokens produced by rotation share the same `family_id`.
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    pub value: String,
+    pub family_id: Uuid,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl RefreshToken {
+    pub fn generate(family_id: Uuid, ttl: Duration) -> Result<Self> {
+        let mut bytes = [0u8; TOKEN_LENGTH];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| eyre!("Failed to generate random bytes for refresh token"))?;
+        let issued_at = Utc::now();
+
+        Ok(Self {
+            value: base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes),
+            family_id,
+            issued_at,
+            expires_at: issued_at + ttl,
+        })
+    }
+
+    pub fn hash(&self) -> Vec<u8> {
+        hash_refresh_token(&self.value)
+    }
+}
+
+pub fn hash_refresh_token(token: &str) -> Vec<u8> {
+    digest(&SHA256, token.as_bytes()).as_ref().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_are_unique() {
+        let ttl = Duration::days(1);
+        let first = RefreshToken::generate(Uuid::new_v4(), ttl).unwrap();
+        let second = RefreshToken::generate(Uuid::new_v4(), ttl).unwrap();
+
+        assert_ne!(first.value, second.value);
+        assert_ne!(first.hash(), second.hash());
+    }
+
+    #[test]
+    fn hash_is_deterministic() {
+        let token = RefreshToken::generate(Uuid::new_v4(), Duration::days(1)).unwrap();
+
+        assert_eq!(token.hash(), hash_refresh_token(&token.value));
+    }
+}
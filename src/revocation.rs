@@ -0,0 +1,254 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    events::{Event, EventBus},
+    jwt::Claims,
+};
+
+/// Upper bound of cached entries, after reaching it stale entries are evicted.
+const MAX_CACHED_ENTRIES: usize = 10_000;
+
+/// List of revoked access tokens backed by database.
+///
+/// Lookups are cached in memory, so most of authenticated requests don't hit database.
+/// Revoked status is cached until the token expires, while not revoked status is cached
+/// only for `cache_ttl`, which bounds the delay of revocations made by other instances.
+///
+/// Revocations are published to `events`, so sessions of the token are ended at once.
+#[derive(Clone)]
+pub struct RevocationList {
+    db_pool: PgPool,
+    cache: Arc<RwLock<HashMap<Uuid, CachedStatus>>>,
+    cache_ttl: Duration,
+    events: EventBus,
+}
+
+#[derive(Clone, Copy)]
+struct CachedStatus {
+    revoked: bool,
+    valid_until: Instant,
+}
+
+impl RevocationList {
+    pub fn new(db_pool: PgPool, cache_ttl: Duration, events: EventBus) -> Self {
+        Self {
+            db_pool,
+            cache: Default::default(),
+            cache_ttl,
+            events,
+        }
+    }
+
+    #[instrument(name = "Check token revocation", skip_all)]
+    pub async fn is_revoked(&self, claims: &Claims) -> Result<bool, sqlx::Error> {
+        if let Some(revoked) = self.cached(&claims.jti) {
+            return Ok(revoked);
+        }
+
+        let revoked = is_token_revoked_db(&claims.jti, &self.db_pool).await?;
+        let valid_until = if revoked {
+            token_deadline(claims)
+        } else {
+            Instant::now() + self.cache_ttl
+        };
+        self.cache_status(claims.jti, revoked, valid_until);
+
+        Ok(revoked)
+    }
+
+    #[instrument(name = "Revoke token", skip_all)]
+    pub async fn revoke(&self, claims: &Claims) -> Result<(), sqlx::Error> {
+        self.revoke_issued(&IssuedToken {
+            jti: claims.jti,
+            account_id: claims.sub,
+            expires_at: claims.expires_at(),
+        })
+        .await
+    }
+
+    /// Revoke access tokens issued to the wallet for the account, which haven't expired yet.
+    #[instrument(name = "Revoke tokens of wallet", skip(self))]
+    pub async fn revoke_tokens_of_wallet(
+        &self,
+        user_id: &str,
+        account_id: &Uuid,
+    ) -> Result<(), sqlx::Error> {
+        for token in get_issued_tokens_of_wallet_db(user_id, account_id, &self.db_pool).await? {
+            self.revoke_issued(&token).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn revoke_issued(&self, token: &IssuedToken) -> Result<(), sqlx::Error> {
+        insert_revoked_token_db(token, &self.db_pool).await?;
+        self.cache_status(token.jti, true, deadline(token.expires_at));
+        self.events.publish(Event::SessionRevoked {
+            account_id: token.account_id,
+            jti: token.jti,
+        });
+
+        Ok(())
+    }
+
+    fn cached(&self, jti: &Uuid) -> Option<bool> {
+        let cache = self.cache.read().unwrap_or_else(|e| e.into_inner());
+
+        cache
+            .get(jti)
+            .filter(|status| status.valid_until > Instant::now())
+            .map(|status| status.revoked)
+    }
+
+    fn cache_status(&self, jti: Uuid, revoked: bool, valid_until: Instant) {
+        let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+        if cache.len() >= MAX_CACHED_ENTRIES {
+            let now = Instant::now();
+            cache.retain(|_, status| status.valid_until > now);
+        }
+
+        cache.insert(
+            jti,
+            CachedStatus {
+                revoked,
+                valid_until,
+            },
+        );
+    }
+}
+
+/// Access token as it's remembered from the moment of issuing.
+struct IssuedToken {
+    jti: Uuid,
+    account_id: Uuid,
+    expires_at: DateTime<Utc>,
+}
+
+/// Moment after which the token is rejected regardless of revocation.
+pub fn token_deadline(claims: &Claims) -> Instant {
+    deadline(claims.expires_at())
+}
+
+fn deadline(expires_at: DateTime<Utc>) -> Instant {
+    let remaining = (expires_at - Utc::now()).to_std().unwrap_or_default();
+
+    Instant::now() + remaining
+}
+
+#[instrument(name = "Check token revocation in database", skip(db_pool))]
+async fn is_token_revoked_db(jti: &Uuid, db_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let ret = sqlx::query!(
+        r#"
+        select exists(select 1 from revoked_tokens where jti = $1) as "revoked!"
+        "#,
+        jti
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(ret.revoked)
+}
+
+/// Delete revocations of expired tokens, which are rejected anyway.
+#[instrument(
+    name = "Delete expired revocations from database",
+    skip_all,
+    level = "debug"
+)]
+pub async fn delete_expired_revocations_db(db_pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let ret = sqlx::query!(
+        r#"
+        delete from revoked_tokens where expires_at < now()
+        "#
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(ret.rows_affected())
+}
+
+/// Delete issued tokens, which have expired and can't be revoked anymore.
+#[instrument(
+    name = "Delete expired issued tokens from database",
+    skip_all,
+    level = "debug"
+)]
+pub async fn delete_expired_issued_tokens_db(db_pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let ret = sqlx::query!(
+        r#"
+        delete from issued_tokens where expires_at < now()
+        "#
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(ret.rows_affected())
+}
+
+/// Remember the issued access token, so it can be revoked along with the wallet.
+#[instrument(name = "Store issued token into database", skip_all)]
+pub async fn insert_issued_token_db(
+    claims: &Claims,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        insert into issued_tokens(jti, account_id, user_id, expires_at)
+        values ($1, $2, $3, $4)
+        "#,
+        claims.jti,
+        claims.sub,
+        claims.wallet,
+        claims.expires_at(),
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Get issued tokens of wallet from database", skip(db_pool))]
+async fn get_issued_tokens_of_wallet_db(
+    user_id: &str,
+    account_id: &Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<IssuedToken>, sqlx::Error> {
+    sqlx::query_as!(
+        IssuedToken,
+        r#"
+        select jti, account_id, expires_at from issued_tokens
+        where user_id = $1 and account_id = $2 and expires_at > now()
+        "#,
+        user_id,
+        account_id
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+#[instrument(name = "Store revoked token into database", skip_all)]
+async fn insert_revoked_token_db(token: &IssuedToken, db_pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        insert into revoked_tokens(jti, account_id, expires_at, revoked_at)
+        values ($1, $2, $3, now())
+        on conflict (jti) do nothing
+        "#,
+        token.jti,
+        token.account_id,
+        token.expires_at,
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
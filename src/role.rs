@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use utoipa::ToSchema;
+
+/// Role of the user, which is stored in database and emitted in `roles` claim of access token.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    EnumString,
+    Display,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Role {
+    /// Granted to every user on sign up.
+    Player,
+    Moderator,
+    /// Passes any role requirement.
+    Admin,
+    /// Game servers and other internal tooling.
+    Service,
+}
+
+/// Role, which is required by [`Authorized`](crate::routes::Authorized) extractor.
+pub trait RequiredRole {
+    const ROLE: Role;
+}
+
+pub struct Moderator;
+
+impl RequiredRole for Moderator {
+    const ROLE: Role = Role::Moderator;
+}
+
+pub struct Admin;
+
+impl RequiredRole for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+pub struct Service;
+
+impl RequiredRole for Service {
+    const ROLE: Role = Role::Service;
+}
@@ -0,0 +1,181 @@
+use axum::{
+    extract::{FromRequestParts, MatchedPath, Path, State},
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ethers::prelude::{Address, U256};
+use eyre::{Report, WrapErr};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use thiserror::Error;
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::{
+    address::ToHex,
+    ethereum::EthereumError,
+    nft::{requirements_met, CollectionHoldings, NftInspector},
+    routes::{
+        get_linked_wallets_db, json_error, json_success, AuthError, AuthRejection, ErrorResponse,
+        JsonResponse, SharedState, User,
+    },
+};
+
+/// Get NFTs of configured collections held by the wallets of the authenticated user.
+#[utoipa::path(
+    get,
+    path = "/me/assets",
+    tag = "assets",
+    responses(
+        AuthRejection,
+        (status = 200, body = JsonResponse<Vec<CollectionHoldings>>),
+        (status = 502, description = "Ethereum node has failed", body = ErrorResponse),
+        (status = 504, description = "Ethereum node hasn't responded in time", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+#[instrument(name = "Get assets", skip_all, fields(account_id = %user.id), err(Debug))]
+pub async fn get_my_assets(
+    user: User,
+    State(nft): State<NftInspector>,
+    State(db_pool): State<PgPool>,
+) -> Result<impl IntoResponse, AssetError> {
+    let owners = get_linked_wallets_db(&user.claims.wallet, &db_pool)
+        .await
+        .wrap_err("Failed to get linked wallets")?
+        .iter()
+        .map(|wallet| wallet.parse::<Address>())
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err("Failed to parse wallet address")?;
+    let holdings = nft.holdings_of(&owners).await?;
+
+    Ok(json_success(holdings))
+}
+
+/// Owner of the token according to indexed `Transfer` events.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TokenOwner {
+    pub contract: String,
+    pub token_id: String,
+    pub owner: String,
+    /// Block of the transfer to the owner.
+    pub block_number: i64,
+}
+
+/// Get the current owner of the token from the index, without asking Ethereum node.
+#[utoipa::path(
+    get,
+    path = "/contracts/{contract}/tokens/{token_id}",
+    tag = "assets",
+    params(
+        ("contract" = String, Path, description = "Address of the collection"),
+        ("token_id" = String, Path, description = "Decimal or hex id of the token"),
+    ),
+    responses(
+        (status = 200, body = JsonResponse<TokenOwner>),
+        (status = 400, description = "Invalid address or token id", body = ErrorResponse),
+        (status = 404, description = "Transfers of the token aren't indexed", body = ErrorResponse),
+    )
+)]
+#[instrument(name = "Get token owner", skip(db_pool), err(Debug))]
+pub async fn get_token_owner(
+    Path((contract, token_id)): Path<(String, String)>,
+    State(db_pool): State<PgPool>,
+) -> Result<impl IntoResponse, AssetError> {
+    let contract = contract
+        .parse::<Address>()
+        .map_err(|e| AssetError::Validation(format!("Failed to parse contract: {e}")))?
+        .to_hex();
+    let token_id = U256::from_dec_str(&token_id)
+        .map_err(|e| AssetError::Validation(format!("Failed to parse token id: {e}")))?
+        .to_string();
+
+    let owner = get_token_owner_db(&contract, &token_id, &db_pool)
+        .await
+        .wrap_err("Failed to get token owner")?
+        .ok_or(AssetError::TokenNotFound)?;
+
+    Ok(json_success(owner))
+}
+
+/// Refuse access to the routes, which require NFT collections, if the access token lacks them.
+pub async fn nft_gate<B: Send>(
+    State(state): State<SharedState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let required = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|path| state.nft.route_requirements(path.as_str()));
+    let Some(required) = required else {
+        return next.run(request).await;
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let user = match User::from_request_parts(&mut parts, &state).await {
+        Ok(user) => user,
+        Err(e) => return e.into_response(),
+    };
+    if !requirements_met(required, &user.claims.collections) {
+        return AuthError::MissingCollection.into_response();
+    }
+
+    next.run(Request::from_parts(parts, body)).await
+}
+
+/// Burned tokens are owned by zero address and considered absent.
+#[instrument(name = "Get token owner from database", skip(db_pool))]
+async fn get_token_owner_db(
+    contract: &str,
+    token_id: &str,
+    db_pool: &PgPool,
+) -> Result<Option<TokenOwner>, sqlx::Error> {
+    sqlx::query_as!(
+        TokenOwner,
+        r#"
+        select contract, token_id, owner, block_number from token_owners
+        where contract = $1 and token_id = $2 and owner != $3
+        "#,
+        contract,
+        token_id,
+        Address::zero().to_hex()
+    )
+    .fetch_optional(db_pool)
+    .await
+}
+
+#[derive(Error, Debug)]
+pub enum AssetError {
+    #[error("Validation error: {0}")]
+    Validation(String),
+    #[error("Token isn't found")]
+    TokenNotFound,
+    #[error("Failed to get NFT holdings: {0}")]
+    Ethereum(#[from] EthereumError),
+    #[error("Unexpected error: {0}")]
+    Unexpected(#[from] Report),
+}
+
+impl IntoResponse for AssetError {
+    fn into_response(self) -> Response {
+        let status_code = match self {
+            AssetError::Validation(_) => StatusCode::BAD_REQUEST,
+            AssetError::TokenNotFound => StatusCode::NOT_FOUND,
+            AssetError::Ethereum(EthereumError::Timeout) => StatusCode::GATEWAY_TIMEOUT,
+            AssetError::Ethereum(EthereumError::Request(_)) => StatusCode::BAD_GATEWAY,
+            AssetError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        // Failures of Ethereum node and database are logged, clients get only the status.
+        let error = if status_code.is_server_error() {
+            status_code
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_owned()
+        } else {
+            self.to_string()
+        };
+        (status_code, json_error(error)).into_response()
+    }
+}
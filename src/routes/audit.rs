@@ -0,0 +1,296 @@
+use std::{fmt::Display, net::IpAddr};
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Query, State},
+    http::{header::USER_AGENT, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use ethers::prelude::Address;
+use eyre::{Report, WrapErr};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use strum::{Display as StrumDisplay, EnumString};
+use thiserror::Error;
+use tower_http::request_id::RequestId;
+use tracing::{error, instrument};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    address::ToHex,
+    rate_limit::RateLimiter,
+    role,
+    routes::{
+        client_ip, json_error, json_success, AuthRejection, Authorized, ErrorResponse,
+        JsonResponse, User,
+    },
+};
+
+/// Default and maximal number of events on a page.
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumString, StrumDisplay)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AuthEventKind {
+    NonceIssued,
+    SignIn,
+    TokenRefresh,
+    TokenRevocation,
+    Logout,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumString, StrumDisplay)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AuthEventOutcome {
+    Success,
+    Failure,
+}
+
+/// Origin of the request, which is stored along with the events it causes.
+#[derive(Debug, Clone, Default)]
+pub struct RequestMeta {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    /// Id assigned to the request by `MakeRequestUuid`.
+    pub request_id: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestMeta
+where
+    S: Send + Sync,
+    RateLimiter: FromRef<S>,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let trust_forwarded_for = RateLimiter::from_ref(state).trust_forwarded_for();
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(512).collect());
+        let request_id = parts
+            .extensions
+            .get::<RequestId>()
+            .and_then(|request_id| request_id.header_value().to_str().ok())
+            .map(ToOwned::to_owned);
+
+        Ok(Self {
+            ip: client_ip(&parts.headers, &parts.extensions, trust_forwarded_for),
+            user_agent,
+            request_id,
+        })
+    }
+}
+
+/// Entry of the audit log, which is filled in while the request is handled.
+pub struct AuthEvent {
+    kind: AuthEventKind,
+    pub account_id: Option<Uuid>,
+    pub user_id: Option<String>,
+    meta: RequestMeta,
+}
+
+impl AuthEvent {
+    pub fn new(kind: AuthEventKind, meta: RequestMeta) -> Self {
+        Self {
+            kind,
+            account_id: None,
+            user_id: None,
+            meta,
+        }
+    }
+
+    /// Event of the authenticated user.
+    pub fn of_user(kind: AuthEventKind, meta: RequestMeta, user: &User) -> Self {
+        Self {
+            kind,
+            account_id: Some(user.id),
+            user_id: Some(user.claims.wallet.clone()),
+            meta,
+        }
+    }
+
+    /// Append the event with the outcome of the request to the audit log.
+    ///
+    /// Failures to write the log are reported, but don't fail the request.
+    pub async fn record<T, E: Display>(self, result: &Result<T, E>, db_pool: &PgPool) {
+        let (outcome, failure_reason) = match result {
+            Ok(_) => (AuthEventOutcome::Success, None),
+            Err(e) => (AuthEventOutcome::Failure, Some(e.to_string())),
+        };
+        if let Err(e) = insert_auth_event_db(&self, outcome, failure_reason, db_pool).await {
+            error!("Failed to record {} auth event: {e}", self.kind);
+        }
+    }
+}
+
+/// Stored event of the audit log.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AuthEventRecord {
+    pub id: i64,
+    pub kind: String,
+    pub outcome: String,
+    pub failure_reason: Option<String>,
+    pub account_id: Option<Uuid>,
+    pub user_id: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthEventsQuery {
+    /// Address of the wallet the events are related to.
+    pub user_id: Option<String>,
+    /// Inclusive lower bound of the event time.
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound of the event time.
+    pub to: Option<DateTime<Utc>>,
+    /// Id of the last event of the previous page.
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Page of events, from the newest to the oldest.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AuthEventsPage {
+    pub events: Vec<AuthEventRecord>,
+    /// Value of `before` parameter to get the next page, absent on the last page.
+    pub next: Option<i64>,
+}
+
+/// Query the audit log, which is available to admins only.
+#[utoipa::path(
+    get,
+    path = "/auth_events",
+    tag = "audit",
+    params(AuthEventsQuery),
+    responses(
+        AuthRejection,
+        (status = 200, body = JsonResponse<AuthEventsPage>),
+        (status = 400, description = "Invalid query", body = ErrorResponse),
+        (status = 403, description = "User isn't admin", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+#[instrument(
+    name = "Get auth events endpoint handler",
+    err(Debug),
+    skip(admin, db_pool),
+    fields(admin_id = %admin.user.id)
+)]
+pub async fn get_auth_events(
+    admin: Authorized<role::Admin>,
+    Query(query): Query<AuthEventsQuery>,
+    State(db_pool): State<PgPool>,
+) -> Result<impl IntoResponse, AuditError> {
+    let user_id = query
+        .user_id
+        .as_deref()
+        .map(|user_id| {
+            user_id
+                .parse::<Address>()
+                .map(|address| address.to_hex())
+                .map_err(|e| AuditError::Validation(format!("Failed to parse user id: {e}")))
+        })
+        .transpose()?;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AuditError::Validation(format!(
+            "Limit must be between 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+
+    let events = get_auth_events_db(&query, user_id.as_deref(), limit, &db_pool)
+        .await
+        .wrap_err("Failed to get auth events")?;
+    let next = match events.last() {
+        Some(last) if events.len() as i64 == limit => Some(last.id),
+        _ => None,
+    };
+
+    Ok(json_success(AuthEventsPage { events, next }))
+}
+
+#[instrument(name = "Store auth event into database", skip_all)]
+async fn insert_auth_event_db(
+    event: &AuthEvent,
+    outcome: AuthEventOutcome,
+    failure_reason: Option<String>,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        insert into auth_events(kind, outcome, failure_reason, account_id, user_id, ip, user_agent, request_id)
+        values ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        event.kind.to_string(),
+        outcome.to_string(),
+        failure_reason,
+        event.account_id,
+        event.user_id,
+        event.meta.ip.map(|ip| ip.to_string()),
+        event.meta.user_agent,
+        event.meta.request_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Get auth events from database", skip(db_pool))]
+async fn get_auth_events_db(
+    query: &AuthEventsQuery,
+    user_id: Option<&str>,
+    limit: i64,
+    db_pool: &PgPool,
+) -> Result<Vec<AuthEventRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        AuthEventRecord,
+        r#"
+        select id, kind, outcome, failure_reason, account_id, user_id, ip, user_agent, request_id, created_at
+        from auth_events
+        where ($1::varchar is null or user_id = $1)
+          and ($2::timestamptz is null or created_at >= $2)
+          and ($3::timestamptz is null or created_at < $3)
+          and ($4::bigint is null or id < $4)
+        order by id desc
+        limit $5
+        "#,
+        user_id,
+        query.from,
+        query.to,
+        query.before,
+        limit
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("Validation error: {0}")]
+    Validation(String),
+    #[error("Unexpected error: {0}")]
+    Unexpected(#[from] Report),
+}
+
+impl IntoResponse for AuditError {
+    fn into_response(self) -> Response {
+        let status_code = match self {
+            AuditError::Validation(_) => StatusCode::BAD_REQUEST,
+            AuditError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status_code, json_error(self.to_string())).into_response()
+    }
+}
@@ -2,99 +2,583 @@ use axum::{
     async_trait,
     extract::{FromRef, FromRequestParts, Json, State},
     headers::{authorization::Bearer, Authorization},
-    http::{request::Parts, StatusCode},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
     response::{IntoResponse, Response},
     RequestPartsExt, TypedHeader,
 };
-use ethers::prelude::{Address, Signature, SignatureError};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use ethers::{
+    prelude::{Address, Bytes, Signature, SignatureError, H256},
+    types::transaction::eip712::Eip712,
+    utils::hash_message,
+};
 use eyre::{Report, Result, WrapErr};
-use serde::Deserialize;
-use serde_json::json;
-use sqlx::PgPool;
+use serde::{Deserialize, Serialize};
+use siwe::Message;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::marker::PhantomData;
+use strum::IntoStaticStr;
 use thiserror::Error;
-use tracing::instrument;
+use tracing::{error, instrument, warn};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
     address::ToHex,
-    jwt::Jwt,
-    routes::{json_error, json_success},
+    config::{ChainsConfig, Eip712Config, RefreshTokenConfig, SessionConfig, SiweConfig},
+    ens::EnsResolver,
+    ethereum::{Ethereum, EthereumError},
+    jwt::{Claims, Jwt, Subject},
+    metrics::Metrics,
+    nft::{held_collections, NftInspector},
+    nonce::{Nonce, NoncePurpose},
+    refresh_token::RefreshToken,
+    revocation::{insert_issued_token_db, RevocationList},
+    role::RequiredRole,
+    routes::{
+        create_account_db, get_account_id_db, get_account_roles_db, insert_refresh_token_db,
+        json_error, update_last_login_db, verify_csrf, AuthEvent, AuthEventKind, ErrorResponse,
+        IssuedSession, IssuedTokens, JsonResponse, RequestMeta, SessionMode, ACCESS_TOKEN_COOKIE,
+    },
 };
 
-#[derive(Deserialize)]
+/// The way user signs the challenge.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignMode {
+    /// EIP-4361 message signed with `personal_sign`.
+    #[default]
+    PersonalSign,
+    /// EIP-712 typed data signed with `eth_signTypedData_v4`.
+    TypedData,
+}
+
+/// Signed challenge, which proves ownership of the wallet.
+#[derive(Deserialize, ToSchema)]
 pub struct Payload {
-    pub user_id: String,
+    #[serde(default)]
+    pub mode: SignMode,
+    /// Signed EIP-4361 message, required in `personal_sign` mode.
+    pub message: Option<String>,
+    /// Address and nonce of the signed typed data, required in `typed_data` mode.
+    pub user_id: Option<String>,
+    pub nonce: Option<Uuid>,
     pub signature: String,
+    /// Whether tokens are returned in the body or set as cookies.
+    #[serde(default)]
+    pub session: SessionMode,
+}
+
+impl Payload {
+    /// Address of the wallet, which the payload claims to be signed by.
+    pub fn claimed_address(&self) -> Option<Address> {
+        claimed_address(self.user_id.as_deref(), self.message.as_deref())
+    }
+}
+
+/// Address from `user_id` of typed data or from the sign-in message, whichever is present.
+pub(super) fn claimed_address(user_id: Option<&str>, message: Option<&str>) -> Option<Address> {
+    match (user_id, message) {
+        (Some(user_id), _) => user_id.parse().ok(),
+        (None, Some(message)) => message
+            .parse::<Message>()
+            .ok()
+            .map(|message| Address::from(message.address)),
+        (None, None) => None,
+    }
+}
+
+pub enum SignedChallenge {
+    PersonalSign(Box<Message>),
+    /// Typed data isn't sent back, we compose it again from the stored nonce.
+    TypedData {
+        user_id: Address,
+        nonce: Uuid,
+    },
 }
 
 pub struct ValidatedPayload {
-    pub user_id: Address,
-    pub signature: Signature,
+    pub challenge: SignedChallenge,
+    /// Either 65 bytes ECDSA signature or arbitrary bytes verified by a contract wallet.
+    pub signature: Bytes,
 }
 
 impl TryFrom<Payload> for ValidatedPayload {
     type Error = String;
 
     #[instrument(name = "Validating payload", skip_all)]
-    fn try_from(Payload { user_id, signature }: Payload) -> Result<Self, Self::Error> {
-        let user_id = user_id
-            .parse()
-            .map_err(|e| format!("Failed to validate user_id: {e}"))?;
-        let signature = signature
+    fn try_from(payload: Payload) -> Result<Self, Self::Error> {
+        let challenge = match payload.mode {
+            SignMode::PersonalSign => {
+                let message = payload
+                    .message
+                    .ok_or("Failed to validate message: message is missing")?
+                    .parse()
+                    .map_err(|e| format!("Failed to validate message: {e}"))?;
+
+                SignedChallenge::PersonalSign(Box::new(message))
+            }
+            SignMode::TypedData => {
+                let user_id = payload
+                    .user_id
+                    .ok_or("Failed to validate user_id: user_id is missing")?
+                    .parse()
+                    .map_err(|e| format!("Failed to validate user_id: {e}"))?;
+                let nonce = payload
+                    .nonce
+                    .ok_or("Failed to validate nonce: nonce is missing")?;
+
+                SignedChallenge::TypedData { user_id, nonce }
+            }
+        };
+        let signature = payload
+            .signature
             .parse()
             .map_err(|e| format!("Failed to validate signature: {e}"))?;
 
-        Ok(Self { user_id, signature })
+        Ok(Self {
+            challenge,
+            signature,
+        })
     }
 }
 
+/// Sign in with the signed challenge, issuing access and refresh tokens.
+#[utoipa::path(
+    post,
+    path = "/web3_auth",
+    tag = "auth",
+    request_body = Payload,
+    responses(
+        (status = 200, description = "Issued session", body = JsonResponse<IssuedSession>),
+        (status = 400, description = "Malformed payload", body = ErrorResponse),
+        (status = 401, description = "Invalid signature or challenge", body = ErrorResponse),
+        (status = 403, description = "Required NFT collections aren't held", body = ErrorResponse),
+        (status = 429, description = "Too many requests"),
+        (status = 502, description = "Ethereum node has failed", body = ErrorResponse),
+        (status = 504, description = "Ethereum node hasn't responded in time", body = ErrorResponse),
+    )
+)]
+#[allow(clippy::too_many_arguments)]
 #[instrument(name = "Web3 auth", skip_all, err(Debug))]
 pub async fn web3_auth(
+    meta: RequestMeta,
     State(jwt): State<Jwt>,
+    State(siwe_config): State<SiweConfig>,
+    State(eip712_config): State<Eip712Config>,
+    State(chains_config): State<ChainsConfig>,
+    State(refresh_token_config): State<RefreshTokenConfig>,
+    State(ethereum): State<Option<Ethereum>>,
+    State(ens): State<EnsResolver>,
+    State(nft): State<NftInspector>,
+    State(session_config): State<SessionConfig>,
+    State(metrics): State<Metrics>,
     State(db_pool): State<PgPool>,
     Json(payload): Json<Payload>,
-) -> Result<impl IntoResponse, AuthError> {
-    let ValidatedPayload { user_id, signature } =
-        payload.try_into().map_err(AuthError::Validation)?;
+) -> Result<Response, AuthError> {
+    let mut event = AuthEvent::new(AuthEventKind::SignIn, meta);
+    event.user_id = payload.claimed_address().map(|address| address.to_hex());
+    let result = match payload.session.check(&session_config) {
+        Ok(mode) => sign_in(
+            &mut event,
+            payload,
+            &jwt,
+            &siwe_config,
+            &eip712_config,
+            &chains_config,
+            &refresh_token_config,
+            ethereum.as_ref(),
+            &ens,
+            &nft,
+            &db_pool,
+        )
+        .await
+        .map(|tokens| (tokens, mode)),
+        Err(e) => Err(e),
+    };
+    metrics.observe_login(result.as_ref().err().map(<&str>::from));
+    event.record(&result, &db_pool).await;
+
+    let (tokens, mode) = result?;
+    tokens.respond(mode, &session_config)
+}
+
+/// Issue tokens for the wallet, which has signed the challenge, creating its account if needed.
+#[allow(clippy::too_many_arguments)]
+async fn sign_in(
+    event: &mut AuthEvent,
+    payload: Payload,
+    jwt: &Jwt,
+    siwe_config: &SiweConfig,
+    eip712_config: &Eip712Config,
+    chains_config: &ChainsConfig,
+    refresh_token_config: &RefreshTokenConfig,
+    ethereum: Option<&Ethereum>,
+    ens: &EnsResolver,
+    nft: &NftInspector,
+    db_pool: &PgPool,
+) -> Result<IssuedTokens, AuthError> {
+    let wallet = verify_wallet(
+        payload,
+        NoncePurpose::SignIn,
+        siwe_config,
+        eip712_config,
+        chains_config,
+        ethereum,
+        db_pool,
+    )
+    .await?;
+    let ens_name = ens
+        .name_of(wallet.address)
+        .await
+        .wrap_err("Failed to get ENS name")?;
+    let collections = check_collections(nft, &wallet.user_id, db_pool).await?;
+
+    let mut tx = db_pool
+        .begin()
+        .await
+        .wrap_err("Failed to start sql transaction")?;
+    let consumed = consume_nonce_db(&wallet.nonce, &wallet.user_id, &mut tx)
+        .await
+        .wrap_err("Failed to consume nonce")?;
+    if !consumed {
+        return Err(AuthError::ConsumedNonce);
+    }
+
+    let account_id = get_account_id_db(&wallet.user_id, &mut tx)
+        .await
+        .wrap_err("Failed to get account of wallet")?;
+    let account_id = match account_id {
+        Some(account_id) => account_id,
+        None => create_account_db(&wallet.user_id, &mut tx)
+            .await
+            .wrap_err("Failed to create account")?,
+    };
+    event.account_id = Some(account_id);
+
+    let refresh_token = RefreshToken::generate(Uuid::new_v4(), refresh_token_config.ttl())?;
+    insert_refresh_token_db(
+        &account_id,
+        &wallet.user_id,
+        wallet.chain_id,
+        &refresh_token,
+        &mut tx,
+    )
+    .await
+    .wrap_err("Failed to store refresh token")?;
+    let roles = get_account_roles_db(&account_id, &mut tx)
+        .await
+        .wrap_err("Failed to get account roles")?;
+    update_last_login_db(&account_id, &mut tx)
+        .await
+        .wrap_err("Failed to update last login")?;
+    let (jwt_token, claims) = jwt.encode(Subject {
+        account_id,
+        wallet: wallet.user_id,
+        ens: ens_name,
+        chain_id: wallet.chain_id,
+        roles,
+        collections,
+    })?;
+    insert_issued_token_db(&claims, &mut tx)
+        .await
+        .wrap_err("Failed to store issued token")?;
+    tx.commit()
+        .await
+        .wrap_err("Failed to commit sql transaction")?;
+
+    Ok(IssuedTokens {
+        jwt: jwt_token,
+        jwk: jwt.jwk().clone(),
+        refresh_token,
+    })
+}
+
+/// Names of NFT collections held by the wallet and the wallets linked to the same account.
+///
+/// Fails if the login gate isn't passed. Without the gate failures of Ethereum node aren't
+/// fatal, the token is issued without collections instead.
+#[instrument(name = "Checking NFT collections", skip(nft, db_pool))]
+pub(super) async fn check_collections(
+    nft: &NftInspector,
+    user_id: &str,
+    db_pool: &PgPool,
+) -> Result<Vec<String>, AuthError> {
+    if !nft.enabled() {
+        return Ok(Vec::new());
+    }
+
+    let owners = get_linked_wallets_db(user_id, db_pool)
+        .await
+        .wrap_err("Failed to get linked wallets")?
+        .iter()
+        .map(|wallet| wallet.parse::<Address>())
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err("Failed to parse wallet address")?;
+    let collections = match nft.holdings_of(&owners).await {
+        Ok(holdings) => held_collections(&holdings),
+        Err(e) if nft.login_gated() => return Err(AuthError::Assets(e)),
+        Err(e) => {
+            warn!("Failed to check NFT holdings of {user_id}: {e}");
+
+            Vec::new()
+        }
+    };
+    if !nft.login_allowed(&collections) {
+        return Err(AuthError::MissingCollection);
+    }
+
+    Ok(collections)
+}
+
+/// Wallet, which has proven its ownership by signing the challenge.
+///
+/// Nonce of the challenge isn't consumed yet, it's up to the caller to do it
+/// along with the changes it authorizes.
+pub(super) struct VerifiedWallet {
+    pub address: Address,
+    pub user_id: String,
+    pub nonce: Uuid,
+    pub chain_id: u64,
+}
+
+/// Check that the payload contains a valid signature of a challenge we have issued
+/// for the `purpose`.
+#[instrument(name = "Verifying wallet", skip_all)]
+pub(super) async fn verify_wallet(
+    payload: Payload,
+    purpose: NoncePurpose,
+    siwe_config: &SiweConfig,
+    eip712_config: &Eip712Config,
+    chains_config: &ChainsConfig,
+    ethereum: Option<&Ethereum>,
+    db_pool: &PgPool,
+) -> Result<VerifiedWallet, AuthError> {
+    let ValidatedPayload {
+        challenge,
+        signature,
+    } = payload.try_into().map_err(AuthError::Validation)?;
+    let (user_id, nonce) = match &challenge {
+        SignedChallenge::PersonalSign(message) => {
+            let nonce = message
+                .nonce
+                .parse()
+                .map_err(|_| AuthError::NonceMismatch)?;
+
+            (Address::from(message.address), nonce)
+        }
+        SignedChallenge::TypedData { user_id, nonce } => (*user_id, *nonce),
+    };
     let user_id_string = user_id.to_hex();
-    let nonce = get_user_nonce_db(&user_id_string, &db_pool)
+    let stored_nonce = get_nonce_db(&nonce, &user_id_string, db_pool)
         .await
-        .wrap_err("Failed to get nonce for user")?;
+        .wrap_err("Failed to get nonce for user")?
+        .ok_or(AuthError::NonceMismatch)?;
+    if stored_nonce.consumed_at.is_some() {
+        return Err(AuthError::ConsumedNonce);
+    }
+    if stored_nonce.expires_at <= Utc::now() {
+        return Err(AuthError::ExpiredNonce);
+    }
+    if NoncePurpose::from_db(&stored_nonce.purpose, stored_nonce.account_id) != Some(purpose) {
+        return Err(AuthError::PurposeMismatch);
+    }
+    let chain_id = stored_nonce.chain_id as u64;
+
+    let signed_hash = match challenge {
+        SignedChallenge::PersonalSign(message) => {
+            validate_message(&message, chain_id, purpose, siwe_config, chains_config)?;
 
-    signature
-        .verify(nonce.to_string(), user_id)
-        .map_err(AuthError::SignatureVerification)?;
+            hash_message(message.to_string())
+        }
+        SignedChallenge::TypedData { .. } => {
+            let nonce = Nonce {
+                value: nonce,
+                issued_at: stored_nonce.issued_at,
+                expires_at: stored_nonce.expires_at,
+                chain_id,
+                purpose,
+            };
+            let typed_data = eip712_config.typed_data(user_id, &nonce)?;
 
-    let jwt_token = jwt.encode(user_id_string.clone())?;
-    let body = json!({
-        "jwt": jwt_token,
-        "jwk": jwt.jwk()
-    });
+            typed_data
+                .encode_eip712()
+                .map(H256::from)
+                .wrap_err("Failed to hash typed data")?
+        }
+    };
+    verify_signature(signed_hash, &signature, user_id, ethereum).await?;
 
-    Ok(json_success(body))
+    Ok(VerifiedWallet {
+        address: user_id,
+        user_id: user_id_string,
+        nonce,
+        chain_id,
+    })
+}
+
+/// Check that the message was issued by us for the chain of the nonce and is valid at the moment.
+#[instrument(name = "Validating signed message", skip_all)]
+fn validate_message(
+    message: &Message,
+    chain_id: u64,
+    purpose: NoncePurpose,
+    config: &SiweConfig,
+    chains_config: &ChainsConfig,
+) -> Result<(), AuthError> {
+    if message.domain.as_str() != config.domain {
+        return Err(AuthError::DomainMismatch);
+    }
+
+    if message.uri.as_str() != config.uri {
+        return Err(AuthError::UriMismatch);
+    }
+
+    if message.statement != config.statement(purpose) {
+        return Err(AuthError::StatementMismatch);
+    }
+
+    if !chains_config.is_allowed(message.chain_id) {
+        return Err(AuthError::ChainNotAllowed);
+    }
+
+    if message.chain_id != chain_id {
+        return Err(AuthError::ChainIdMismatch);
+    }
+
+    let now = Utc::now().timestamp();
+    if let Some(expiration_time) = &message.expiration_time {
+        if expiration_time.as_ref().unix_timestamp() <= now {
+            return Err(AuthError::ExpiredMessage);
+        }
+    }
+
+    if let Some(not_before) = &message.not_before {
+        if not_before.as_ref().unix_timestamp() > now {
+            return Err(AuthError::PrematureMessage);
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify signature of externally owned account, falling back to EIP-1271 for contract wallets.
+#[instrument(name = "Verifying signature", skip(signature, ethereum))]
+async fn verify_signature(
+    hash: H256,
+    signature: &Bytes,
+    address: Address,
+    ethereum: Option<&Ethereum>,
+) -> Result<(), AuthError> {
+    let eoa_verification = Signature::try_from(signature.as_ref())
+        .and_then(|signature| signature.verify(hash, address));
+    let Err(eoa_error) = eoa_verification else {
+        return Ok(());
+    };
+
+    let Some(ethereum) = ethereum else {
+        return Err(AuthError::SignatureVerification(eoa_error));
+    };
+
+    match ethereum
+        .is_valid_contract_signature(address, hash, signature.clone())
+        .await?
+    {
+        Some(true) => Ok(()),
+        Some(false) => Err(AuthError::ContractSignatureRejected),
+        None => Err(AuthError::SignatureVerification(eoa_error)),
+    }
+}
+
+struct StoredNonce {
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+    chain_id: i64,
+    purpose: String,
+    account_id: Option<Uuid>,
 }
 
 #[instrument(name = "Get nonce for user from database", skip(db_pool))]
-async fn get_user_nonce_db(user_id: &str, db_pool: &PgPool) -> Result<Uuid, sqlx::Error> {
+async fn get_nonce_db(
+    nonce: &Uuid,
+    user_id: &str,
+    db_pool: &PgPool,
+) -> Result<Option<StoredNonce>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredNonce,
+        r#"
+        select issued_at, expires_at, consumed_at, chain_id, purpose, account_id from nonces
+        where nonce = $1 and user_id = $2
+        "#,
+        nonce,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await
+}
+
+/// Get addresses of the wallet and the wallets linked to the same account.
+#[instrument(name = "Get linked wallets from database", skip(db_pool))]
+pub(super) async fn get_linked_wallets_db(
+    user_id: &str,
+    db_pool: &PgPool,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        select user_id from users
+        where user_id = $1
+           or account_id = (select account_id from users where user_id = $1)
+        "#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+/// Mark the nonce of the wallet as used.
+///
+/// Returns `false` if it has been already consumed, expired or issued to another wallet.
+#[instrument(name = "Consume nonce in database", skip(tx))]
+pub(super) async fn consume_nonce_db(
+    nonce: &Uuid,
+    user_id: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<bool, sqlx::Error> {
     let ret = sqlx::query!(
         r#"
-        select nonce from users where user_id = $1
-       "#,
+        update nonces set consumed_at = now()
+        where nonce = $1 and user_id = $2 and consumed_at is null and expires_at > now()
+        "#,
+        nonce,
         user_id
     )
-    .fetch_one(db_pool)
+    .execute(&mut *tx)
     .await?;
 
-    Ok(ret.nonce)
+    Ok(ret.rows_affected() == 1)
 }
 
-pub struct User(pub String);
+/// Authenticated user, whose access token is valid and isn't revoked.
+///
+/// The token is taken from `Authorization` header or, if cookie sessions are enabled,
+/// from the access token cookie. Requests authenticated by cookie, which change state,
+/// have to pass CSRF check.
+pub struct User {
+    /// Id of the account.
+    pub id: Uuid,
+    pub claims: Claims,
+}
 
 #[async_trait]
 impl<S> FromRequestParts<S> for User
 where
     S: Send + Sync,
     Jwt: FromRef<S>,
+    RevocationList: FromRef<S>,
+    SessionConfig: FromRef<S>,
+    Metrics: FromRef<S>,
 {
     type Rejection = AuthError;
 
@@ -102,32 +586,160 @@ where
         parts: &mut Parts,
         state: &S,
     ) -> std::result::Result<Self, Self::Rejection> {
-        let bearer: TypedHeader<Authorization<Bearer>> = parts
-            .extract()
-            .await
-            .map_err(|_| AuthError::InvalidAuthToken)?;
+        let token = if parts.headers.contains_key(AUTHORIZATION) {
+            let bearer: TypedHeader<Authorization<Bearer>> = parts
+                .extract()
+                .await
+                .map_err(|_| AuthError::InvalidAuthToken)?;
+
+            bearer.token().to_owned()
+        } else {
+            let jar = CookieJar::from_headers(&parts.headers);
+            let cookie = jar
+                .get(ACCESS_TOKEN_COOKIE)
+                .filter(|_| SessionConfig::from_ref(state).cookies_enabled)
+                .ok_or(AuthError::InvalidAuthToken)?;
+            verify_csrf(&parts.method, &parts.headers, &jar)?;
+
+            cookie.value().to_owned()
+        };
 
         let jwt = Jwt::from_ref(state);
+        let revocation_list = RevocationList::from_ref(state);
+
+        let claims = verify_token(&token, &jwt, &revocation_list)
+            .await
+            .map_err(|e| {
+                if matches!(
+                    e,
+                    AuthError::InvalidAuthToken
+                        | AuthError::ExpiredAuthToken
+                        | AuthError::RevokedAuthToken
+                ) {
+                    Metrics::from_ref(state).observe_jwt_verification_failure((&e).into());
+                }
+
+                e
+            })?;
+
+        Ok(User {
+            id: claims.sub,
+            claims,
+        })
+    }
+}
+
+/// Decode the access token and check that it's neither expired nor revoked.
+pub(super) async fn verify_token(
+    token: &str,
+    jwt: &Jwt,
+    revocation_list: &RevocationList,
+) -> Result<Claims, AuthError> {
+    let claims = jwt.decode(token).map_err(|_| AuthError::InvalidAuthToken)?;
+    if claims.expired() {
+        return Err(AuthError::ExpiredAuthToken);
+    }
 
-        let claims = jwt.decode(bearer.token())?;
-        if claims.expired() {
-            return Err(AuthError::ExpiredAuthToken);
+    let revoked = revocation_list
+        .is_revoked(&claims)
+        .await
+        .wrap_err("Failed to check token revocation")?;
+    if revoked {
+        return Err(AuthError::RevokedAuthToken);
+    }
+
+    Ok(claims)
+}
+
+/// Authenticated user, who has the role `R`.
+///
+/// Roles are read from the access token, so changes of roles take effect after refresh.
+pub struct Authorized<R> {
+    pub user: User,
+    role: PhantomData<R>,
+}
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for Authorized<R>
+where
+    S: Send + Sync,
+    Jwt: FromRef<S>,
+    RevocationList: FromRef<S>,
+    SessionConfig: FromRef<S>,
+    Metrics: FromRef<S>,
+    R: RequiredRole,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let user = User::from_request_parts(parts, state).await?;
+        if !user.claims.has_role(R::ROLE) {
+            return Err(AuthError::Forbidden);
         }
 
-        Ok(User(claims.sub))
+        Ok(Authorized {
+            user,
+            role: PhantomData,
+        })
     }
 }
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum AuthError {
     #[error("Validation error: {0}")]
     Validation(String),
     #[error("Signature verification error: {0}")]
     SignatureVerification(#[from] SignatureError),
+    #[error("Contract wallet rejected the signature")]
+    ContractSignatureRejected,
+    #[error("Failed to verify signature with contract wallet: {0}")]
+    ContractWallet(#[from] EthereumError),
+    #[error("Message domain doesn't match")]
+    DomainMismatch,
+    #[error("Message uri doesn't match")]
+    UriMismatch,
+    #[error("Message statement doesn't match")]
+    StatementMismatch,
+    #[error("Message chain id doesn't match")]
+    ChainIdMismatch,
+    #[error("Chain is not allowed")]
+    ChainNotAllowed,
+    #[error("Message nonce doesn't match")]
+    NonceMismatch,
+    #[error("Nonce has been issued for another purpose")]
+    PurposeMismatch,
+    #[error("Nonce is expired")]
+    ExpiredNonce,
+    #[error("Nonce has been already used")]
+    ConsumedNonce,
+    #[error("Message is expired")]
+    ExpiredMessage,
+    #[error("Message is not valid yet")]
+    PrematureMessage,
     #[error("Header doesn't contain correct type of auth token")]
     InvalidAuthToken,
     #[error("Expired auth token")]
     ExpiredAuthToken,
+    #[error("Revoked auth token")]
+    RevokedAuthToken,
+    #[error("Insufficient permissions")]
+    Forbidden,
+    #[error("CSRF token is missing or doesn't match")]
+    CsrfMismatch,
+    #[error("Failed to check NFT holdings: {0}")]
+    Assets(EthereumError),
+    #[error("Required NFT collection isn't held")]
+    MissingCollection,
+    #[error("Refresh token is invalid or revoked")]
+    InvalidRefreshToken,
+    #[error("Refresh token is expired")]
+    ExpiredRefreshToken,
+    #[error("Refresh token has been already used")]
+    RefreshTokenReuse,
     #[error("Unexpected error: {0}")]
     Unexpected(#[from] Report),
 }
@@ -136,12 +748,44 @@ impl IntoResponse for AuthError {
     fn into_response(self) -> Response {
         let status_code = match self {
             AuthError::Validation(_) => StatusCode::BAD_REQUEST,
-            AuthError::SignatureVerification(_) => StatusCode::UNAUTHORIZED,
+            AuthError::SignatureVerification(_) | AuthError::ContractSignatureRejected => {
+                StatusCode::UNAUTHORIZED
+            }
+            AuthError::ContractWallet(EthereumError::Timeout) => StatusCode::GATEWAY_TIMEOUT,
+            AuthError::ContractWallet(EthereumError::Request(_)) => StatusCode::BAD_GATEWAY,
+            AuthError::Assets(EthereumError::Timeout) => StatusCode::GATEWAY_TIMEOUT,
+            AuthError::Assets(EthereumError::Request(_)) => StatusCode::BAD_GATEWAY,
+            AuthError::DomainMismatch
+            | AuthError::UriMismatch
+            | AuthError::StatementMismatch
+            | AuthError::ChainIdMismatch
+            | AuthError::ChainNotAllowed
+            | AuthError::NonceMismatch
+            | AuthError::PurposeMismatch
+            | AuthError::ExpiredNonce
+            | AuthError::ConsumedNonce
+            | AuthError::ExpiredMessage
+            | AuthError::PrematureMessage => StatusCode::UNAUTHORIZED,
             AuthError::InvalidAuthToken => StatusCode::BAD_REQUEST,
-            AuthError::ExpiredAuthToken => StatusCode::UNAUTHORIZED,
+            AuthError::ExpiredAuthToken | AuthError::RevokedAuthToken => StatusCode::UNAUTHORIZED,
+            AuthError::Forbidden | AuthError::MissingCollection | AuthError::CsrfMismatch => {
+                StatusCode::FORBIDDEN
+            }
+            AuthError::InvalidRefreshToken
+            | AuthError::ExpiredRefreshToken
+            | AuthError::RefreshTokenReuse => StatusCode::UNAUTHORIZED,
             AuthError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
-        let error = json_error(self.to_string());
-        (status_code, error).into_response()
+        // Failures of Ethereum node and database are logged, clients get only the status.
+        let error = if status_code.is_server_error() {
+            error!("{self:?}");
+            status_code
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_owned()
+        } else {
+            self.to_string()
+        };
+        (status_code, json_error(error)).into_response()
     }
 }
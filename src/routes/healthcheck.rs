@@ -1,3 +1,156 @@
+use std::{collections::BTreeMap, fmt::Display, future::Future, time::Duration};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use sqlx::{migrate::Migrator, PgPool};
+use tokio::time::Instant;
+use tracing::{error, instrument, warn};
+use utoipa::ToSchema;
+
+use crate::{ethereum::Ethereum, routes::JsonResponse};
+
+/// Upper bound of a single dependency check.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Check that the service is running, used as liveness probe.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses((status = 200, description = "Service is running"))
+)]
 pub async fn healthcheck() -> impl axum::response::IntoResponse {
     axum::http::StatusCode::OK
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyStatus {
+    Up,
+    Down,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct DependencyCheck {
+    pub status: DependencyStatus,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Checks of the dependencies by their names, the service is ready if all of them are up.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct Readiness {
+    pub checks: BTreeMap<String, DependencyCheck>,
+}
+
+/// Check that the service can handle requests, i.e. database is reachable and migrated,
+/// and Ethereum node responds, if it's configured.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "All dependencies are up", body = JsonResponse<Readiness>),
+        (status = 503, description = "Some dependency is down", body = JsonResponse<Readiness>),
+    )
+)]
+#[instrument(name = "Readiness endpoint handler", skip_all)]
+pub async fn readiness(
+    State(ethereum): State<Option<Ethereum>>,
+    State(migrator): State<&'static Migrator>,
+    State(db_pool): State<PgPool>,
+) -> impl IntoResponse {
+    let (database, migrations, ethereum) = tokio::join!(
+        check("database", ping_db(&db_pool)),
+        check("migrations", check_migrations_db(migrator, &db_pool)),
+        async {
+            match ethereum {
+                Some(ethereum) => Some(check("ethereum", ethereum.block_number()).await),
+                None => None,
+            }
+        },
+    );
+    let mut checks = BTreeMap::from([
+        ("database".to_owned(), database),
+        ("migrations".to_owned(), migrations),
+    ]);
+    if let Some(ethereum) = ethereum {
+        checks.insert("ethereum".to_owned(), ethereum);
+    }
+
+    let readiness = Readiness { checks };
+    if readiness
+        .checks
+        .values()
+        .all(|check| check.status == DependencyStatus::Up)
+    {
+        (StatusCode::OK, JsonResponse::Success(readiness))
+    } else {
+        warn!("Service isn't ready: {readiness:?}");
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            JsonResponse::Error(readiness),
+        )
+    }
+}
+
+/// Run the check within `CHECK_TIMEOUT`, measuring its latency.
+///
+/// Endpoint isn't authenticated, so the cause of a failure is only logged.
+async fn check<T, E: Display>(
+    dependency: &str,
+    request: impl Future<Output = Result<T, E>>,
+) -> DependencyCheck {
+    let started_at = Instant::now();
+    let result = tokio::time::timeout(CHECK_TIMEOUT, request).await;
+    let latency_ms = started_at.elapsed().as_millis() as u64;
+    let error = match result {
+        Ok(Ok(_)) => None,
+        Ok(Err(e)) => {
+            error!("Check of {dependency} failed: {e:#}");
+            Some("Check failed".to_owned())
+        }
+        Err(_) => Some("Check timed out".to_owned()),
+    };
+
+    DependencyCheck {
+        status: match error {
+            None => DependencyStatus::Up,
+            Some(_) => DependencyStatus::Down,
+        },
+        latency_ms,
+        error,
+    }
+}
+
+#[instrument(name = "Ping database", skip_all)]
+async fn ping_db(db_pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!("select 1 as one").fetch_one(db_pool).await?;
+
+    Ok(())
+}
+
+/// Check that all migrations of `migrator` have been successfully applied.
+#[instrument(name = "Check migrations in database", skip_all)]
+async fn check_migrations_db(migrator: &Migrator, db_pool: &PgPool) -> Result<()> {
+    let applied = sqlx::query_scalar!(
+        r#"
+        select version from _sqlx_migrations
+        where success
+        "#
+    )
+    .fetch_all(db_pool)
+    .await
+    .wrap_err("Failed to get applied migrations")?;
+    let pending = migrator
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| migration.version.to_string())
+        .collect::<Vec<_>>();
+    if !pending.is_empty() {
+        return Err(eyre!("Pending migrations: {}", pending.join(", ")));
+    }
+
+    Ok(())
+}
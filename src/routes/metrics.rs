@@ -0,0 +1,70 @@
+use std::time::Instant;
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use eyre::Report;
+use sqlx::PgPool;
+use thiserror::Error;
+use tracing::instrument;
+
+use crate::{metrics::Metrics, routes::json_error};
+
+/// Count requests and observe their latency by matched route and response status.
+///
+/// Requests, which don't match any route, aren't tracked to keep the number of series bounded.
+pub async fn track_metrics(
+    State(metrics): State<Metrics>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let Some(route) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+    else {
+        return next.run(request).await;
+    };
+    let method = request.method().clone();
+    let started_at = Instant::now();
+
+    let response = next.run(request).await;
+    metrics.observe_request(
+        method.as_str(),
+        &route,
+        response.status().as_u16(),
+        started_at.elapsed(),
+    );
+
+    response
+}
+
+/// Metrics in Prometheus text format.
+#[instrument(name = "Get metrics endpoint handler", skip_all, err(Debug))]
+pub async fn get_metrics(
+    State(metrics): State<Metrics>,
+    State(db_pool): State<PgPool>,
+) -> Result<impl IntoResponse, MetricsError> {
+    let body = metrics.render(&db_pool)?;
+
+    Ok(([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], body))
+}
+
+#[derive(Error, Debug)]
+pub enum MetricsError {
+    #[error("Unexpected error: {0}")]
+    Unexpected(#[from] Report),
+}
+
+impl IntoResponse for MetricsError {
+    fn into_response(self) -> Response {
+        let status_code = match self {
+            MetricsError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status_code, json_error(self.to_string())).into_response()
+    }
+}
@@ -1,59 +1,155 @@
-use std::fmt::Display;
+use std::fmt::{self, Debug, Display};
 
 use axum::{
     extract::FromRef,
-    http::Request,
+    http::{header, HeaderMap, Request},
+    middleware,
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Json, Router,
 };
+use opentelemetry::global;
+use opentelemetry_http::HeaderExtractor;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{migrate::Migrator, PgPool};
+use tokio_util::sync::CancellationToken;
 use tower::ServiceBuilder;
 use tower_http::{
+    cors::CorsLayer,
     request_id::{MakeRequestId, RequestId},
-    trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
+    trace::{DefaultOnResponse, MakeSpan, TraceLayer},
     ServiceBuilderExt,
 };
-use tracing::{instrument, Level};
+use tracing::{info_span, instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+pub use assets::*;
+pub use audit::*;
 pub use auth::*;
 pub use healthcheck::*;
+pub use metrics::*;
+pub use openapi::*;
+pub use rate_limit::*;
+pub use session::*;
+pub use tokens::*;
 pub use users::*;
+pub use wallets::*;
+pub use well_known::*;
+pub use ws::*;
 
-use crate::jwt::Jwt;
+use crate::{
+    config::{
+        ChainsConfig, Eip712Config, NonceConfig, OpenApiConfig, RefreshTokenConfig, SessionConfig,
+        SiweConfig, WebSocketConfig,
+    },
+    ens::EnsResolver,
+    ethereum::Ethereum,
+    events::EventBus,
+    jwt::Jwt,
+    metrics::Metrics,
+    nft::NftInspector,
+    rate_limit::RateLimiter,
+    revocation::RevocationList,
+};
 
+mod assets;
+mod audit;
 mod auth;
 mod healthcheck;
+mod metrics;
+mod openapi;
+mod rate_limit;
+mod session;
+mod tokens;
 mod users;
+mod wallets;
+mod well_known;
+mod ws;
 
 #[instrument(name = "Setup routes", skip_all)]
-pub fn setup_router(state: SharedState) -> Router {
+pub fn setup_router(state: SharedState, cors: CorsLayer) -> Router {
     let request_id_layer = ServiceBuilder::new()
         .set_x_request_id(MakeRequestUuid)
         .layer(
             TraceLayer::new_for_http()
-                .make_span_with(
-                    DefaultMakeSpan::new()
-                        .include_headers(true)
-                        .level(Level::INFO),
-                )
-                .on_response(DefaultOnResponse::new().include_headers(true)),
+                .make_span_with(MakeRequestSpan)
+                // Response headers aren't recorded, since `set-cookie` carries tokens.
+                .on_response(DefaultOnResponse::new()),
         )
         .propagate_x_request_id();
 
-    Router::new()
+    let mut router = Router::new()
         .route("/healthcheck", get(healthcheck))
+        .route("/health/live", get(healthcheck))
+        .route("/health/ready", get(readiness))
+        .route("/.well-known/jwks.json", get(jwks))
+        .route(
+            "/.well-known/openid-configuration",
+            get(openid_configuration),
+        )
+        .route("/auth_events", get(get_auth_events))
+        .route(
+            "/contracts/:contract/tokens/:token_id",
+            get(get_token_owner),
+        )
+        .route("/me", get(get_me).patch(update_me))
+        .route("/me/assets", get(get_my_assets))
+        .route("/me/wallets", post(link_wallet))
+        .route("/me/wallets/:user_id", delete(unlink_wallet))
+        .route("/me/wallets/:user_id/nonce", get(set_link_nonce))
+        .route("/users/:user_id", get(get_user))
         .route("/users/:user_id/nonce", get(set_nonce_for_address))
+        .route("/users/:user_id/roles", put(set_user_roles))
         .route("/web3_auth", post(web3_auth))
+        .route("/token/refresh", post(refresh_token))
+        .route("/tokens/revoke", post(revoke_token))
+        .route("/logout", post(logout))
+        .route("/ws", get(ws))
+        .route("/openapi.json", get(openapi_json));
+    if state.openapi.docs_enabled {
+        router = router.route("/docs", get(docs));
+    }
+
+    router
+        .route_layer(middleware::from_fn_with_state(state.clone(), nft_gate))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(state.clone(), track_metrics))
         .with_state(state)
+        .layer(cors)
         .layer(request_id_layer)
 }
 
+/// Router of `/metrics`, which is either merged into the public router or served on the admin port.
+pub fn setup_metrics_router(state: SharedState) -> Router {
+    Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(state)
+}
+
 #[derive(Clone, FromRef)]
 pub struct SharedState {
     pub jwt: Jwt,
+    pub siwe: SiweConfig,
+    pub eip712: Eip712Config,
+    pub chains: ChainsConfig,
+    pub nonce: NonceConfig,
+    pub refresh_token: RefreshTokenConfig,
+    pub session: SessionConfig,
+    pub websocket: WebSocketConfig,
+    pub openapi: OpenApiConfig,
+    pub revocation_list: RevocationList,
+    pub rate_limiter: RateLimiter,
+    pub metrics: Metrics,
+    pub events: EventBus,
+    /// Cancelled on shutdown signal, which ends long-lived connections.
+    pub shutdown: CancellationToken,
+    pub ethereum: Option<Ethereum>,
+    pub ens: EnsResolver,
+    pub nft: NftInspector,
+    /// Migrations the database is expected to have, checked by readiness probe.
+    pub migrator: &'static Migrator,
     pub db_pool: PgPool,
 }
 
@@ -68,7 +164,61 @@ impl MakeRequestId for MakeRequestUuid {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Span of the request, which continues the trace passed by the caller in `traceparent` header.
+#[derive(Clone)]
+struct MakeRequestSpan;
+
+impl<B> MakeSpan<B> for MakeRequestSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let request_id = request
+            .extensions()
+            .get::<RequestId>()
+            .and_then(|request_id| request_id.header_value().to_str().ok());
+        let span = info_span!(
+            "request",
+            method = %request.method(),
+            uri = %request.uri(),
+            version = ?request.version(),
+            request_id,
+            headers = ?RedactedHeaders(request.headers()),
+        );
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(request.headers()))
+        });
+        span.set_parent(parent);
+
+        span
+    }
+}
+
+/// Headers to record in traces, with credentials replaced by a placeholder.
+struct RedactedHeaders<'a>(&'a HeaderMap);
+
+impl RedactedHeaders<'_> {
+    const SENSITIVE: [header::HeaderName; 3] = [
+        header::AUTHORIZATION,
+        header::COOKIE,
+        header::PROXY_AUTHORIZATION,
+    ];
+}
+
+impl Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(name, value)| {
+                let value: &dyn Debug = if Self::SENSITIVE.contains(name) {
+                    &"[redacted]"
+                } else {
+                    value
+                };
+                (name, value)
+            }))
+            .finish()
+    }
+}
+
+/// Envelope of JSON responses, either `{"success": ...}` or `{"error": ...}`.
+#[derive(Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum JsonResponse<T> {
     Success(T),
@@ -0,0 +1,167 @@
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+    Json,
+};
+use utoipa::{
+    openapi::{
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+        Deprecated, OpenApi as Specification,
+    },
+    IntoResponses, Modify, OpenApi, Path, ToSchema,
+};
+use utoipa_redoc::Redoc;
+
+use super::{
+    assets, audit, auth, healthcheck, tokens, users, wallets, well_known, ws, ACCESS_TOKEN_COOKIE,
+};
+use crate::config::OpenApiConfig;
+
+/// OpenAPI specification of the public routes.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Battlemon Ethereum",
+        description = "Sign-in with Ethereum wallets. Bodies of JSON responses are wrapped \
+                       into `{\"success\": ...}` or `{\"error\": ...}` envelope."
+    ),
+    paths(
+        healthcheck::healthcheck,
+        healthcheck::readiness,
+        well_known::jwks,
+        well_known::openid_configuration,
+        audit::get_auth_events,
+        assets::get_token_owner,
+        users::get_me,
+        users::update_me,
+        assets::get_my_assets,
+        wallets::set_link_nonce,
+        wallets::link_wallet,
+        wallets::unlink_wallet,
+        users::get_user,
+        users::set_nonce_for_address,
+        users::set_user_roles,
+        auth::web3_auth,
+        tokens::refresh_token,
+        tokens::revoke_token,
+        tokens::logout,
+        ws::ws,
+        openapi_json,
+    ),
+    modifiers(&SecuritySchemes, &LegacyHealthcheck),
+    tags(
+        (name = "auth", description = "Sign-in with a signed challenge"),
+        (name = "tokens", description = "Refresh and revocation of issued tokens"),
+        (name = "users", description = "Profiles and roles of users"),
+        (name = "wallets", description = "Wallets linked to the account"),
+        (name = "assets", description = "NFTs of configured collections"),
+        (name = "audit", description = "Log of authentication events"),
+        (name = "events", description = "Events pushed over WebSocket"),
+        (name = "health", description = "Probes of orchestrator"),
+        (name = "well-known", description = "Keys and discovery document of token issuer"),
+        (name = "docs", description = "This specification"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Specification of the routes served with `config`.
+pub fn specification(config: &OpenApiConfig) -> Specification {
+    let mut specification = ApiDoc::openapi();
+    if config.docs_enabled {
+        DocsRoute.modify(&mut specification);
+    }
+
+    specification
+}
+
+/// Body of failed responses, i.e. `JsonResponse::Error` with the message.
+#[derive(ToSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+/// Rejections of [`User`](super::User) extractor, shared by the routes requiring authentication.
+#[derive(IntoResponses)]
+pub enum AuthRejection {
+    /// Access token is missing or malformed.
+    #[response(status = 400)]
+    InvalidToken(#[to_schema] ErrorResponse),
+    /// Access token is expired or revoked.
+    #[response(status = 401)]
+    RejectedToken(#[to_schema] ErrorResponse),
+    /// Request authenticated by cookie lacks matching `x-csrf-token` header.
+    #[response(status = 403)]
+    CsrfMismatch(#[to_schema] ErrorResponse),
+}
+
+/// Serve OpenAPI specification of the public routes.
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "docs",
+    responses((status = 200, description = "OpenAPI 3 document", body = Object))
+)]
+pub async fn openapi_json(State(config): State<OpenApiConfig>) -> impl IntoResponse {
+    Json(specification(&config))
+}
+
+/// Serve documentation page rendered from the specification by Redoc, which is loaded from CDN.
+#[utoipa::path(
+    get,
+    path = "/docs",
+    tag = "docs",
+    responses((status = 200, description = "Documentation page", content_type = "text/html", body = String))
+)]
+pub async fn docs(State(config): State<OpenApiConfig>) -> impl IntoResponse {
+    Html(Redoc::new(specification(&config)).to_html())
+}
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut Specification) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(ACCESS_TOKEN_COOKIE))),
+        );
+    }
+}
+
+/// `/healthcheck`, which is kept as an alias of `/health/live` for older deployments.
+struct LegacyHealthcheck;
+
+impl Modify for LegacyHealthcheck {
+    fn modify(&self, openapi: &mut Specification) {
+        let Some(mut item) = openapi.paths.paths.get("/health/live").cloned() else {
+            return;
+        };
+        if let Some(operation) = item.get.as_mut() {
+            operation.operation_id = Some("legacy_healthcheck".to_owned());
+            operation.deprecated = Some(Deprecated::True);
+        }
+        openapi.paths.paths.insert("/healthcheck".to_owned(), item);
+    }
+}
+
+/// `/docs`, which is served only if it's enabled.
+struct DocsRoute;
+
+impl Modify for DocsRoute {
+    fn modify(&self, openapi: &mut Specification) {
+        openapi.paths.add_path_operation(
+            __path_docs::path(),
+            __path_docs::methods(),
+            __path_docs::operation(),
+        );
+    }
+}
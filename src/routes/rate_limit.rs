@@ -0,0 +1,158 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{
+        rejection::BytesRejection, ConnectInfo, FromRequest, FromRequestParts, MatchedPath, Path,
+        State,
+    },
+    http::{header::RETRY_AFTER, Extensions, HeaderMap, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ethers::prelude::Address;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    address::ToHex,
+    rate_limit::RateLimiter,
+    routes::{claimed_address, json_error},
+};
+
+/// Limit requests to the configured routes by client IP and by the address they target.
+pub async fn rate_limit(
+    State(limiter): State<RateLimiter>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response, RateLimitError> {
+    let Some(route) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+    else {
+        return Ok(next.run(request).await);
+    };
+    let Some(limits) = limiter.route_limits(&route).cloned() else {
+        return Ok(next.run(request).await);
+    };
+
+    if let Some(per_ip) = &limits.per_ip {
+        if let Some(ip) = client_ip(
+            request.headers(),
+            request.extensions(),
+            limiter.trust_forwarded_for(),
+        ) {
+            limiter
+                .acquire(&route, &format!("ip:{ip}"), per_ip)
+                .map_err(RateLimitError::TooManyRequests)?;
+        }
+    }
+
+    let request = match &limits.per_address {
+        Some(per_address) => {
+            let (address, request) = target_address(request).await?;
+            if let Some(address) = address {
+                limiter
+                    .acquire(&route, &format!("address:{address}"), per_address)
+                    .map_err(RateLimitError::TooManyRequests)?;
+            }
+
+            request
+        }
+        None => request,
+    };
+
+    Ok(next.run(request).await)
+}
+
+/// IP of the client, which is either the peer or the last hop seen by the trusted proxy.
+pub(super) fn client_ip(
+    headers: &HeaderMap,
+    extensions: &Extensions,
+    trust_forwarded_for: bool,
+) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let forwarded_ip = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+        if forwarded_ip.is_some() {
+            return forwarded_ip;
+        }
+    }
+
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip())
+}
+
+/// Fields of the sign-in payload, which contain the address of the wallet.
+#[derive(Deserialize)]
+struct SignedPayload {
+    message: Option<String>,
+    user_id: Option<String>,
+}
+
+/// Address the request is made for, taken from `user_id` path parameter or the signed payload.
+///
+/// The body is buffered to be read, so the request is rebuilt from it.
+async fn target_address(
+    request: Request<Body>,
+) -> Result<(Option<String>, Request<Body>), RateLimitError> {
+    let (mut parts, body) = request.into_parts();
+    if let Ok(Path(params)) =
+        Path::<HashMap<String, String>>::from_request_parts(&mut parts, &()).await
+    {
+        if let Some(user_id) = params.get("user_id") {
+            let address = user_id
+                .parse::<Address>()
+                .map(|address| address.to_hex())
+                .unwrap_or_else(|_| user_id.to_lowercase());
+
+            return Ok((Some(address), Request::from_parts(parts, body)));
+        }
+    }
+
+    let body = Bytes::from_request(Request::new(body), &()).await?;
+    // Malformed payloads are left to the handler to reject.
+    let address = serde_json::from_slice::<SignedPayload>(&body)
+        .ok()
+        .and_then(|payload| claimed_address(payload.user_id.as_deref(), payload.message.as_deref()))
+        .map(|address| address.to_hex());
+
+    Ok((address, Request::from_parts(parts, Body::from(body))))
+}
+
+#[derive(Error, Debug)]
+pub enum RateLimitError {
+    #[error("Too many requests")]
+    TooManyRequests(Duration),
+    #[error(transparent)]
+    Body(#[from] BytesRejection),
+}
+
+impl IntoResponse for RateLimitError {
+    fn into_response(self) -> Response {
+        match self {
+            RateLimitError::TooManyRequests(retry_after) => {
+                let retry_after =
+                    HeaderValue::from(retry_after.as_secs_f64().ceil().max(1.0) as u64);
+                let error = json_error(self.to_string());
+
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(RETRY_AFTER, retry_after)],
+                    error,
+                )
+                    .into_response()
+            }
+            RateLimitError::Body(rejection) => rejection.into_response(),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use axum::{
+    http::{HeaderMap, Method},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use base64::Engine;
+use chrono::Utc;
+use eyre::eyre;
+use jsonwebtoken::jwk::Jwk;
+use ring::{
+    constant_time::verify_slices_are_equal,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use time::Duration;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{SameSitePolicy, SessionConfig},
+    jwt::ACCESS_TOKEN_TTL_SECS,
+    refresh_token::RefreshToken,
+    routes::{json_success, AuthError},
+};
+
+pub const ACCESS_TOKEN_COOKIE: &str = "access_token";
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+/// Cookie readable by the page, whose value has to be repeated in `CSRF_HEADER`.
+pub const CSRF_TOKEN_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+const CSRF_TOKEN_LENGTH: usize = 32;
+
+/// The way issued tokens are delivered to the client.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionMode {
+    /// Tokens are returned in the body of the response.
+    #[default]
+    Token,
+    /// Tokens are set as `HttpOnly` cookies, while the body contains CSRF token.
+    Cookie,
+}
+
+impl SessionMode {
+    pub fn check(self, config: &SessionConfig) -> Result<Self, AuthError> {
+        if self == SessionMode::Cookie && !config.cookies_enabled {
+            return Err(AuthError::Validation(
+                "Cookie sessions are disabled".to_owned(),
+            ));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Body of sign-in and refresh responses, depending on the requested session mode.
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(untagged)]
+pub enum IssuedSession {
+    Token(TokenPair),
+    Cookie(CookieSession),
+}
+
+/// Tokens returned in the body in `token` session mode.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct TokenPair {
+    pub jwt: String,
+    /// Public key, which verifies `jwt`.
+    #[schema(value_type = Object)]
+    pub jwk: Jwk,
+    pub refresh_token: String,
+}
+
+/// Body of `cookie` session mode, while the tokens are set as `HttpOnly` cookies.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct CookieSession {
+    #[schema(value_type = Object)]
+    pub jwk: Jwk,
+    /// Value to repeat in `x-csrf-token` header of state-changing requests.
+    pub csrf_token: String,
+}
+
+/// Tokens issued at sign-in or refresh.
+pub struct IssuedTokens {
+    pub jwt: String,
+    pub jwk: Jwk,
+    pub refresh_token: RefreshToken,
+}
+
+impl IssuedTokens {
+    /// Compose the response, which delivers the tokens in `mode`.
+    ///
+    /// Cookie sessions get a new CSRF token along with the tokens.
+    pub fn respond(self, mode: SessionMode, config: &SessionConfig) -> Result<Response, AuthError> {
+        match mode {
+            SessionMode::Token => Ok(json_success(IssuedSession::Token(TokenPair {
+                jwt: self.jwt,
+                jwk: self.jwk,
+                refresh_token: self.refresh_token.value,
+            }))
+            .into_response()),
+            SessionMode::Cookie => {
+                let session_ttl =
+                    Duration::seconds((self.refresh_token.expires_at - Utc::now()).num_seconds());
+                let csrf_token = generate_csrf_token()?;
+                let jar = CookieJar::new()
+                    .add(session_cookie(
+                        config,
+                        ACCESS_TOKEN_COOKIE,
+                        self.jwt,
+                        Duration::seconds(ACCESS_TOKEN_TTL_SECS),
+                        true,
+                    ))
+                    .add(session_cookie(
+                        config,
+                        REFRESH_TOKEN_COOKIE,
+                        self.refresh_token.value,
+                        session_ttl,
+                        true,
+                    ))
+                    .add(session_cookie(
+                        config,
+                        CSRF_TOKEN_COOKIE,
+                        csrf_token.clone(),
+                        session_ttl,
+                        false,
+                    ));
+                let body = json_success(IssuedSession::Cookie(CookieSession {
+                    jwk: self.jwk,
+                    csrf_token,
+                }));
+
+                Ok((jar, body).into_response())
+            }
+        }
+    }
+}
+
+/// Cookies, which remove the session from the browser.
+pub fn clear_session_cookies(config: &SessionConfig) -> CookieJar {
+    [ACCESS_TOKEN_COOKIE, REFRESH_TOKEN_COOKIE, CSRF_TOKEN_COOKIE]
+        .into_iter()
+        .fold(CookieJar::new(), |jar, name| {
+            let mut cookie = session_cookie(config, name, String::new(), Duration::ZERO, true);
+            cookie.make_removal();
+
+            jar.add(cookie)
+        })
+}
+
+/// Check that state-changing request repeats CSRF cookie in `CSRF_HEADER`.
+///
+/// Pages of other origins can make the browser send cookies, but can't read them.
+pub fn verify_csrf(method: &Method, headers: &HeaderMap, jar: &CookieJar) -> Result<(), AuthError> {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(());
+    }
+
+    let cookie = jar.get(CSRF_TOKEN_COOKIE).map(|cookie| cookie.value());
+    let header = headers
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok());
+    match (cookie, header) {
+        (Some(cookie), Some(header))
+            if !cookie.is_empty()
+                && verify_slices_are_equal(cookie.as_bytes(), header.as_bytes()).is_ok() =>
+        {
+            Ok(())
+        }
+        _ => Err(AuthError::CsrfMismatch),
+    }
+}
+
+fn session_cookie(
+    config: &SessionConfig,
+    name: &'static str,
+    value: String,
+    max_age: Duration,
+    http_only: bool,
+) -> Cookie<'static> {
+    let same_site = match config.same_site {
+        SameSitePolicy::Strict => SameSite::Strict,
+        SameSitePolicy::Lax => SameSite::Lax,
+        SameSitePolicy::None => SameSite::None,
+    };
+    let mut cookie = Cookie::build(name, value)
+        .path("/")
+        .http_only(http_only)
+        .secure(config.secure)
+        .same_site(same_site)
+        .max_age(max_age)
+        .finish();
+    if let Some(domain) = &config.domain {
+        cookie.set_domain(domain.clone());
+    }
+
+    cookie
+}
+
+fn generate_csrf_token() -> Result<String, AuthError> {
+    let mut bytes = [0u8; CSRF_TOKEN_LENGTH];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| eyre!("Failed to generate random bytes for CSRF token"))?;
+
+    Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes))
+}
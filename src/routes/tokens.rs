@@ -0,0 +1,481 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, Method},
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use eyre::WrapErr;
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{instrument, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    config::{RefreshTokenConfig, SessionConfig},
+    ens::EnsResolver,
+    jwt::{Jwt, Subject},
+    nft::NftInspector,
+    refresh_token::{hash_refresh_token, RefreshToken},
+    revocation::{insert_issued_token_db, RevocationList},
+    routes::{
+        check_collections, clear_session_cookies, get_account_roles_db, json_success, verify_csrf,
+        AuthError, AuthEvent, AuthEventKind, AuthRejection, ErrorResponse, IssuedSession,
+        IssuedTokens, JsonResponse, RequestMeta, SessionMode, User, REFRESH_TOKEN_COOKIE,
+    },
+};
+
+#[derive(Deserialize, Default, ToSchema)]
+pub struct RefreshPayload {
+    /// Taken from the refresh token cookie, if missing.
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub session: SessionMode,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LogoutPayload {
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RevokePayload {
+    /// Access or refresh token issued for the authenticated user.
+    pub token: String,
+}
+
+/// Exchange refresh token for a new pair of access and refresh tokens.
+///
+/// Presented token is rotated, i.e. can't be used anymore. If rotated token is presented
+/// again, we treat it as stolen and revoke the whole family of tokens.
+///
+/// Token passed as cookie is exchanged for new cookies and has to pass CSRF check.
+#[utoipa::path(
+    post,
+    path = "/token/refresh",
+    tag = "tokens",
+    request_body(content = Option<RefreshPayload>, description = "Omitted if the token is passed as cookie"),
+    responses(
+        (status = 200, description = "Issued session", body = JsonResponse<IssuedSession>),
+        (status = 400, description = "Refresh token is missing", body = ErrorResponse),
+        (status = 401, description = "Refresh token is invalid, expired or reused", body = ErrorResponse),
+        (status = 403, description = "Cookie lacks matching `x-csrf-token` header", body = ErrorResponse),
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+#[instrument(name = "Refresh token", skip_all, err(Debug))]
+pub async fn refresh_token(
+    meta: RequestMeta,
+    method: Method,
+    headers: HeaderMap,
+    jar: CookieJar,
+    State(jwt): State<Jwt>,
+    State(refresh_token_config): State<RefreshTokenConfig>,
+    State(session_config): State<SessionConfig>,
+    State(ens): State<EnsResolver>,
+    State(nft): State<NftInspector>,
+    State(db_pool): State<PgPool>,
+    payload: Option<Json<RefreshPayload>>,
+) -> Result<Response, AuthError> {
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+    let (refresh_token, mode) = match payload.refresh_token {
+        Some(refresh_token) => (refresh_token, payload.session.check(&session_config)?),
+        None => {
+            let cookie = jar
+                .get(REFRESH_TOKEN_COOKIE)
+                .filter(|_| session_config.cookies_enabled)
+                .ok_or(AuthError::InvalidRefreshToken)?;
+            verify_csrf(&method, &headers, &jar)?;
+
+            (cookie.value().to_owned(), SessionMode::Cookie)
+        }
+    };
+    let mut event = AuthEvent::new(AuthEventKind::TokenRefresh, meta);
+    let result = rotate_refresh_token(
+        &mut event,
+        &refresh_token,
+        &jwt,
+        &refresh_token_config,
+        &ens,
+        &nft,
+        &db_pool,
+    )
+    .await;
+    event.record(&result, &db_pool).await;
+
+    result?.respond(mode, &session_config)
+}
+
+async fn rotate_refresh_token(
+    event: &mut AuthEvent,
+    refresh_token: &str,
+    jwt: &Jwt,
+    refresh_token_config: &RefreshTokenConfig,
+    ens: &EnsResolver,
+    nft: &NftInspector,
+    db_pool: &PgPool,
+) -> Result<IssuedTokens, AuthError> {
+    let token_hash = hash_refresh_token(refresh_token);
+    // Ethereum node is asked while the token row isn't locked, so the token is checked
+    // once more after the lookups in case it was rotated or revoked meanwhile.
+    let (stored_token, tx) = lock_refresh_token(event, &token_hash, db_pool).await?;
+    tx.rollback()
+        .await
+        .wrap_err("Failed to rollback sql transaction")?;
+    // Holdings may have changed since the sign-in, so the login gate is checked again.
+    let collections = check_collections(nft, &stored_token.user_id, db_pool).await?;
+    let ens_name = ens
+        .cached_name_of(&stored_token.user_id)
+        .await
+        .wrap_err("Failed to get ENS name")?;
+
+    let (stored_token, mut tx) = lock_refresh_token(event, &token_hash, db_pool).await?;
+    rotate_refresh_token_db(&token_hash, &mut tx)
+        .await
+        .wrap_err("Failed to rotate refresh token")?;
+    let new_refresh_token =
+        RefreshToken::generate(stored_token.family_id, refresh_token_config.ttl())?;
+    let chain_id = stored_token.chain_id as u64;
+    insert_refresh_token_db(
+        &stored_token.account_id,
+        &stored_token.user_id,
+        chain_id,
+        &new_refresh_token,
+        &mut tx,
+    )
+    .await
+    .wrap_err("Failed to store refresh token")?;
+    let roles = get_account_roles_db(&stored_token.account_id, &mut tx)
+        .await
+        .wrap_err("Failed to get account roles")?;
+    let (jwt_token, claims) = jwt.encode(Subject {
+        account_id: stored_token.account_id,
+        wallet: stored_token.user_id,
+        ens: ens_name,
+        chain_id,
+        roles,
+        collections,
+    })?;
+    insert_issued_token_db(&claims, &mut tx)
+        .await
+        .wrap_err("Failed to store issued token")?;
+    tx.commit()
+        .await
+        .wrap_err("Failed to commit sql transaction")?;
+
+    Ok(IssuedTokens {
+        jwt: jwt_token,
+        jwk: jwt.jwk().clone(),
+        refresh_token: new_refresh_token,
+    })
+}
+
+/// Lock the refresh token row, returning it only if it can still be rotated.
+///
+/// Reuse of an already rotated token revokes the whole family.
+async fn lock_refresh_token(
+    event: &mut AuthEvent,
+    token_hash: &[u8],
+    db_pool: &PgPool,
+) -> Result<(StoredRefreshToken, Transaction<'static, Postgres>), AuthError> {
+    let mut tx = db_pool
+        .begin()
+        .await
+        .wrap_err("Failed to start sql transaction")?;
+
+    let stored_token = get_refresh_token_db(token_hash, &mut tx)
+        .await
+        .wrap_err("Failed to get refresh token")?
+        .ok_or(AuthError::InvalidRefreshToken)?;
+    event.account_id = Some(stored_token.account_id);
+    event.user_id = Some(stored_token.user_id.clone());
+    if stored_token.revoked_at.is_some() {
+        return Err(AuthError::InvalidRefreshToken);
+    }
+
+    if stored_token.rotated_at.is_some() {
+        warn!(
+            "Reuse of rotated refresh token detected, revoking family {}",
+            stored_token.family_id
+        );
+        revoke_refresh_token_family_db(&stored_token.family_id, &mut tx)
+            .await
+            .wrap_err("Failed to revoke refresh token family")?;
+        tx.commit()
+            .await
+            .wrap_err("Failed to commit sql transaction")?;
+
+        return Err(AuthError::RefreshTokenReuse);
+    }
+
+    if stored_token.expires_at <= Utc::now() {
+        return Err(AuthError::ExpiredRefreshToken);
+    }
+
+    Ok((stored_token, tx))
+}
+
+/// Revoke access token of the request and, if passed, the family of refresh token.
+///
+/// Refresh token is taken from the cookie, if it isn't passed in the payload.
+/// Session cookies are removed.
+#[utoipa::path(
+    post,
+    path = "/logout",
+    tag = "tokens",
+    request_body(content = Option<LogoutPayload>),
+    responses(
+        AuthRejection,
+        (status = 200, body = Object, example = json!({"success": null})),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+#[instrument(name = "Logout", skip_all, fields(account_id = %user.id), err(Debug))]
+pub async fn logout(
+    user: User,
+    meta: RequestMeta,
+    jar: CookieJar,
+    State(revocation_list): State<RevocationList>,
+    State(session_config): State<SessionConfig>,
+    State(db_pool): State<PgPool>,
+    payload: Option<Json<LogoutPayload>>,
+) -> Result<impl IntoResponse, AuthError> {
+    let refresh_token = payload
+        .and_then(|Json(payload)| payload.refresh_token)
+        .or_else(|| {
+            jar.get(REFRESH_TOKEN_COOKIE)
+                .filter(|_| session_config.cookies_enabled)
+                .map(|cookie| cookie.value().to_owned())
+        });
+    let event = AuthEvent::of_user(AuthEventKind::Logout, meta, &user);
+    let result = end_session(&user, refresh_token, &revocation_list, &db_pool).await;
+    event.record(&result, &db_pool).await;
+    result?;
+
+    let cookies = session_config
+        .cookies_enabled
+        .then(|| clear_session_cookies(&session_config));
+
+    Ok((cookies, json_success(())))
+}
+
+async fn end_session(
+    user: &User,
+    refresh_token: Option<String>,
+    revocation_list: &RevocationList,
+    db_pool: &PgPool,
+) -> Result<(), AuthError> {
+    revocation_list
+        .revoke(&user.claims)
+        .await
+        .wrap_err("Failed to revoke access token")?;
+
+    if let Some(refresh_token) = refresh_token {
+        revoke_refresh_token_family_of_account_db(
+            &hash_refresh_token(&refresh_token),
+            &user.id,
+            db_pool,
+        )
+        .await
+        .wrap_err("Failed to revoke refresh token")?;
+    }
+
+    Ok(())
+}
+
+/// Revoke any access or refresh token of the authenticated user.
+///
+/// Like RFC 7009 suggests, tokens which are invalid or belong to other users are ignored
+/// and don't cause an error.
+#[utoipa::path(
+    post,
+    path = "/tokens/revoke",
+    tag = "tokens",
+    request_body = RevokePayload,
+    responses(
+        AuthRejection,
+        (status = 200, body = Object, example = json!({"success": null})),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+#[instrument(name = "Revoke token", skip_all, fields(account_id = %user.id), err(Debug))]
+pub async fn revoke_token(
+    user: User,
+    meta: RequestMeta,
+    State(jwt): State<Jwt>,
+    State(revocation_list): State<RevocationList>,
+    State(db_pool): State<PgPool>,
+    Json(RevokePayload { token }): Json<RevokePayload>,
+) -> Result<impl IntoResponse, AuthError> {
+    let event = AuthEvent::of_user(AuthEventKind::TokenRevocation, meta, &user);
+    let result = revoke_token_of_user(&user, &token, &jwt, &revocation_list, &db_pool).await;
+    event.record(&result, &db_pool).await;
+
+    result.map(json_success)
+}
+
+async fn revoke_token_of_user(
+    user: &User,
+    token: &str,
+    jwt: &Jwt,
+    revocation_list: &RevocationList,
+    db_pool: &PgPool,
+) -> Result<(), AuthError> {
+    match jwt.decode(token) {
+        Ok(claims) if claims.sub == user.id => revocation_list
+            .revoke(&claims)
+            .await
+            .wrap_err("Failed to revoke access token")?,
+        Ok(_) => warn!("Attempt to revoke access token of another user"),
+        Err(_) => {
+            revoke_refresh_token_family_of_account_db(&hash_refresh_token(token), &user.id, db_pool)
+                .await
+                .wrap_err("Failed to revoke refresh token")?
+        }
+    }
+
+    Ok(())
+}
+
+struct StoredRefreshToken {
+    family_id: Uuid,
+    account_id: Uuid,
+    user_id: String,
+    chain_id: i64,
+    expires_at: DateTime<Utc>,
+    rotated_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+#[instrument(name = "Get refresh token from database", skip_all)]
+async fn get_refresh_token_db(
+    token_hash: &[u8],
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Option<StoredRefreshToken>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredRefreshToken,
+        r#"
+        select family_id, account_id, user_id, chain_id, expires_at, rotated_at, revoked_at
+        from refresh_tokens
+        where token_hash = $1
+        for update
+        "#,
+        token_hash
+    )
+    .fetch_optional(&mut *tx)
+    .await
+}
+
+#[instrument(name = "Store refresh token into database", skip(refresh_token, tx))]
+pub(super) async fn insert_refresh_token_db(
+    account_id: &Uuid,
+    user_id: &str,
+    chain_id: u64,
+    refresh_token: &RefreshToken,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        insert into refresh_tokens(
+            token_hash, family_id, account_id, user_id, chain_id, issued_at, expires_at
+        )
+        values ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        refresh_token.hash(),
+        refresh_token.family_id,
+        account_id,
+        user_id,
+        chain_id as i64,
+        refresh_token.issued_at,
+        refresh_token.expires_at,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Rotate refresh token in database", skip_all)]
+async fn rotate_refresh_token_db(
+    token_hash: &[u8],
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        update refresh_tokens set rotated_at = now()
+        where token_hash = $1
+        "#,
+        token_hash
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Revoke refresh token family in database", skip(tx))]
+async fn revoke_refresh_token_family_db(
+    family_id: &Uuid,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        update refresh_tokens set revoked_at = now()
+        where family_id = $1 and revoked_at is null
+        "#,
+        family_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Revoke refresh tokens of wallet in database", skip(tx))]
+pub(super) async fn revoke_refresh_tokens_of_wallet_db(
+    user_id: &str,
+    account_id: &Uuid,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        update refresh_tokens set revoked_at = now()
+        where user_id = $1 and account_id = $2 and revoked_at is null
+        "#,
+        user_id,
+        account_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(
+    name = "Revoke refresh token family of account in database",
+    skip(token_hash, db_pool)
+)]
+async fn revoke_refresh_token_family_of_account_db(
+    token_hash: &[u8],
+    account_id: &Uuid,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        update refresh_tokens set revoked_at = now()
+        where family_id = (
+            select family_id from refresh_tokens
+            where token_hash = $1 and account_id = $2
+        )
+        and revoked_at is null
+        "#,
+        token_hash,
+        account_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
@@ -1,57 +1,603 @@
 use crate::{
     address::ToHex,
-    routes::{json_error, json_success},
+    config::{ChainsConfig, Eip712Config, NonceConfig, SiweConfig},
+    ens::{EnsError, EnsResolver},
+    ethereum::EthereumError,
+    events::{Event, EventBus},
+    metrics::Metrics,
+    nonce::{Nonce, NoncePurpose},
+    role::{self, Role},
+    routes::{
+        json_error, json_success, AuthEvent, AuthEventKind, AuthRejection, Authorized,
+        ErrorResponse, JsonResponse, RequestMeta, SignMode, User,
+    },
 };
 use axum::{
-    extract::{Path, State},
+    extract::{Json, Path, Query, State},
     http::StatusCode,
     response::{IntoResponse, Response},
 };
-use ethers::prelude::Address;
-use eyre::WrapErr;
-use sqlx::{PgPool, Postgres, Transaction};
+use chrono::{DateTime, Utc};
+use ethers::{prelude::Address, types::transaction::eip712::TypedData};
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json as SqlJson, PgPool, Postgres, Transaction};
 use thiserror::Error;
 use tracing::{error, instrument};
+use url::Url;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-#[instrument(name = "Set nonce endpoint handler", err(Debug), skip(db_pool))]
+const NICKNAME_LENGTH: std::ops::RangeInclusive<usize> = 3..=32;
+const MAX_AVATAR_URL_LENGTH: usize = 2048;
+/// Postgres error code of unique constraint violation.
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// Data the user has to sign to obtain an auth token, depending on the requested mode.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct Challenge {
+    pub nonce: Uuid,
+    /// Sign-In with Ethereum message to sign with `personal_sign`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// EIP-712 typed data to sign with `eth_signTypedData_v4`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub typed_data: Option<TypedData>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NonceQuery {
+    #[serde(default)]
+    pub mode: SignMode,
+    /// Chain to sign in on, the default one is used if absent.
+    pub chain_id: Option<u64>,
+}
+
+/// Profile of the account, which is shared by all linked wallets.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct Profile {
+    pub account_id: Uuid,
+    pub nickname: Option<String>,
+    pub avatar_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Shown to the owner of the account only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub wallets: Vec<LinkedWallet>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct LinkedWallet {
+    pub address: String,
+    /// Primary ENS name of the wallet, as it was resolved at the last sign-in.
+    pub ens_name: Option<String>,
+}
+
+struct ProfileRow {
+    account_id: Uuid,
+    nickname: Option<String>,
+    avatar_url: Option<String>,
+    created_at: DateTime<Utc>,
+    last_login_at: Option<DateTime<Utc>>,
+    wallets: SqlJson<Vec<LinkedWallet>>,
+}
+
+impl From<ProfileRow> for Profile {
+    fn from(row: ProfileRow) -> Self {
+        Self {
+            account_id: row.account_id,
+            nickname: row.nickname,
+            avatar_url: row.avatar_url,
+            created_at: row.created_at,
+            last_login_at: row.last_login_at,
+            wallets: row.wallets.0,
+        }
+    }
+}
+
+/// Changes of the profile, absent fields are left untouched and `null` clears the field.
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ProfilePatch {
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    #[schema(value_type = Option<String>)]
+    pub nickname: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    #[schema(value_type = Option<String>)]
+    pub avatar_url: Option<Option<String>>,
+}
+
+pub struct ValidatedProfilePatch {
+    pub nickname: Option<Option<String>>,
+    pub avatar_url: Option<Option<Url>>,
+}
+
+impl TryFrom<ProfilePatch> for ValidatedProfilePatch {
+    type Error = String;
+
+    #[instrument(name = "Validating profile patch", skip_all)]
+    fn try_from(patch: ProfilePatch) -> Result<Self, Self::Error> {
+        if let Some(Some(nickname)) = &patch.nickname {
+            if !NICKNAME_LENGTH.contains(&nickname.chars().count()) {
+                return Err(format!(
+                    "Nickname must be from {} to {} characters long",
+                    NICKNAME_LENGTH.start(),
+                    NICKNAME_LENGTH.end()
+                ));
+            }
+            if !nickname
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                return Err(
+                    "Nickname may contain only latin letters, digits and underscores".to_owned(),
+                );
+            }
+        }
+
+        let avatar_url = match patch.avatar_url {
+            Some(Some(avatar_url)) => Some(Some(parse_avatar_url(&avatar_url)?)),
+            Some(None) => Some(None),
+            None => None,
+        };
+
+        Ok(Self {
+            nickname: patch.nickname,
+            avatar_url,
+        })
+    }
+}
+
+fn parse_avatar_url(avatar_url: &str) -> Result<Url, String> {
+    if avatar_url.len() > MAX_AVATAR_URL_LENGTH {
+        return Err(format!(
+            "Avatar url must be at most {MAX_AVATAR_URL_LENGTH} characters long"
+        ));
+    }
+    let avatar_url = Url::parse(avatar_url).map_err(|e| format!("Invalid avatar url: {e}"))?;
+    if !matches!(avatar_url.scheme(), "http" | "https") {
+        return Err("Avatar url must use http or https scheme".to_owned());
+    }
+
+    Ok(avatar_url)
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RolesPayload {
+    pub roles: Vec<Role>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/{user_id}/nonce",
+    tag = "auth",
+    params(("user_id" = String, Path, description = "Address of the wallet"), NonceQuery),
+    responses(
+        (status = 200, description = "Challenge to sign", body = JsonResponse<Challenge>),
+        (status = 400, description = "Invalid address or chain", body = ErrorResponse),
+        (status = 429, description = "Too many requests"),
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+#[instrument(
+    name = "Set nonce endpoint handler",
+    err(Debug),
+    skip(
+        meta,
+        siwe_config,
+        eip712_config,
+        chains_config,
+        nonce_config,
+        metrics,
+        db_pool
+    )
+)]
 pub async fn set_nonce_for_address(
+    meta: RequestMeta,
+    Path(user_id): Path<String>,
+    Query(NonceQuery { mode, chain_id }): Query<NonceQuery>,
+    State(siwe_config): State<SiweConfig>,
+    State(eip712_config): State<Eip712Config>,
+    State(chains_config): State<ChainsConfig>,
+    State(nonce_config): State<NonceConfig>,
+    State(metrics): State<Metrics>,
+    State(db_pool): State<PgPool>,
+) -> Result<impl IntoResponse, UserError> {
+    let mut event = AuthEvent::new(AuthEventKind::NonceIssued, meta);
+    let result = issue_nonce(
+        &mut event,
+        &user_id,
+        NoncePurpose::SignIn,
+        mode,
+        chain_id,
+        &siwe_config,
+        &eip712_config,
+        &chains_config,
+        &nonce_config,
+        &db_pool,
+    )
+    .await;
+    if result.is_ok() {
+        metrics.observe_nonce_issued();
+    }
+    event.record(&result, &db_pool).await;
+
+    result.map(json_success)
+}
+
+/// Store a new nonce for the wallet and compose the challenge of the `purpose` to sign
+/// in the requested mode.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn issue_nonce(
+    event: &mut AuthEvent,
+    user_id: &str,
+    purpose: NoncePurpose,
+    mode: SignMode,
+    chain_id: Option<u64>,
+    siwe_config: &SiweConfig,
+    eip712_config: &Eip712Config,
+    chains_config: &ChainsConfig,
+    nonce_config: &NonceConfig,
+    db_pool: &PgPool,
+) -> Result<Challenge, UserError> {
+    let chain_id = chain_id.unwrap_or(chains_config.default_chain_id);
+    if !chains_config.is_allowed(chain_id) {
+        return Err(UserError::ChainNotAllowed(chain_id));
+    }
+    let user_id = parse_user_id(user_id)?;
+    event.user_id = Some(user_id.to_hex());
+    let nonce = Nonce::new(nonce_config.ttl(), chain_id, purpose);
+    let mut tx = db_pool
+        .begin()
+        .await
+        .wrap_err("Failed to start sql transaction")?;
+
+    let challenge = match mode {
+        SignMode::PersonalSign => Challenge {
+            nonce: nonce.value,
+            message: siwe_config
+                .message(user_id, &nonce)
+                .map(|message| Some(message.to_string()))
+                .wrap_err("Failed to compose message")?,
+            typed_data: None,
+        },
+        SignMode::TypedData => Challenge {
+            nonce: nonce.value,
+            message: None,
+            typed_data: eip712_config
+                .typed_data(user_id, &nonce)
+                .map(Some)
+                .wrap_err("Failed to compose typed data")?,
+        },
+    };
+
+    let user_id = user_id.to_hex();
+    insert_user_db(&user_id, &mut tx)
+        .await
+        .wrap_err("Failed to insert user")?;
+    insert_nonce_db(&user_id, &nonce, &mut tx)
+        .await
+        .wrap_err("Failed to insert nonce for user")?;
+
+    tx.commit()
+        .await
+        .wrap_err("Failed to commit sql transaction")?;
+
+    Ok(challenge)
+}
+
+/// Replace roles of the account the wallet is linked to.
+///
+/// Already issued tokens keep old roles until they are refreshed.
+#[utoipa::path(
+    put,
+    path = "/users/{user_id}/roles",
+    tag = "users",
+    params(("user_id" = String, Path, description = "Address of the wallet")),
+    request_body = RolesPayload,
+    responses(
+        AuthRejection,
+        (status = 200, body = JsonResponse<RolesPayload>),
+        (status = 400, description = "Invalid address", body = ErrorResponse),
+        (status = 403, description = "User isn't admin", body = ErrorResponse),
+        (status = 404, description = "Wallet isn't registered", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+#[instrument(
+    name = "Set roles endpoint handler",
+    err(Debug),
+    skip(admin, db_pool),
+    fields(admin_id = %admin.user.id)
+)]
+pub async fn set_user_roles(
+    admin: Authorized<role::Admin>,
     Path(user_id): Path<String>,
     State(db_pool): State<PgPool>,
+    Json(RolesPayload { roles }): Json<RolesPayload>,
 ) -> Result<impl IntoResponse, UserError> {
-    let nonce = Uuid::new_v4();
-    let user_id: Address = user_id.parse().wrap_err("Failed to parse user id")?;
+    let user_id = parse_user_id(&user_id)?.to_hex();
     let mut tx = db_pool
         .begin()
         .await
         .wrap_err("Failed to start sql transaction")?;
 
-    upsert_nonce_for_user_db(user_id.to_hex(), &nonce, &mut tx)
+    let account_id = get_account_id_db(&user_id, &mut tx)
+        .await
+        .wrap_err("Failed to get account of wallet")?
+        .ok_or(UserError::UserNotFound)?;
+
+    revoke_roles_db(&account_id, &mut tx)
         .await
-        .wrap_err("Failed to upsert nonce for user")?;
+        .wrap_err("Failed to revoke roles")?;
+    for role in roles {
+        grant_role_db(&account_id, role, &mut tx)
+            .await
+            .wrap_err("Failed to grant role")?;
+    }
+    let roles = get_account_roles_db(&account_id, &mut tx)
+        .await
+        .wrap_err("Failed to get account roles")?;
 
     tx.commit()
         .await
         .wrap_err("Failed to commit sql transaction")?;
 
-    Ok(json_success(nonce))
+    Ok(json_success(RolesPayload { roles }))
+}
+
+/// Get profile of the authenticated user.
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "users",
+    responses(AuthRejection, (status = 200, body = JsonResponse<Profile>)),
+    security(("bearer" = []), ("cookie" = []))
+)]
+#[instrument(
+    name = "Get own profile endpoint handler",
+    err(Debug),
+    skip_all,
+    fields(account_id = %user.id)
+)]
+pub async fn get_me(
+    user: User,
+    State(db_pool): State<PgPool>,
+) -> Result<impl IntoResponse, UserError> {
+    let profile = get_profile_db(&user.id, &db_pool)
+        .await
+        .wrap_err("Failed to get profile")?
+        .ok_or(UserError::UserNotFound)?;
+
+    Ok(json_success(profile))
+}
+
+/// Update profile of the authenticated user, omitted fields are kept.
+#[utoipa::path(
+    patch,
+    path = "/me",
+    tag = "users",
+    request_body = ProfilePatch,
+    responses(
+        AuthRejection,
+        (status = 200, body = JsonResponse<Profile>),
+        (status = 400, description = "Invalid nickname or avatar", body = ErrorResponse),
+        (status = 409, description = "Nickname is taken", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+#[instrument(
+    name = "Update own profile endpoint handler",
+    err(Debug),
+    skip_all,
+    fields(account_id = %user.id)
+)]
+pub async fn update_me(
+    user: User,
+    State(events): State<EventBus>,
+    State(db_pool): State<PgPool>,
+    Json(patch): Json<ProfilePatch>,
+) -> Result<impl IntoResponse, UserError> {
+    let patch: ValidatedProfilePatch = patch.try_into().map_err(UserError::Validation)?;
+
+    let profile = update_profile_db(&user.id, &patch, &db_pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                UserError::NicknameTaken
+            }
+            e => UserError::UnexpectedError(
+                eyre::Report::new(e).wrap_err("Failed to update profile"),
+            ),
+        })?
+        .ok_or(UserError::UserNotFound)?;
+    events.publish(Event::ProfileUpdated {
+        account_id: profile.account_id,
+        nickname: profile.nickname.clone(),
+        avatar_url: profile.avatar_url.clone(),
+    });
+
+    Ok(json_success(profile))
+}
+
+/// Get profile of the wallet, which is specified either by address or ENS name.
+#[utoipa::path(
+    get,
+    path = "/users/{user_id}",
+    tag = "users",
+    params(("user_id" = String, Path, description = "Address or ENS name of the wallet")),
+    responses(
+        (status = 200, body = JsonResponse<Profile>),
+        (status = 400, description = "Invalid address", body = ErrorResponse),
+        (status = 404, description = "Wallet isn't registered", body = ErrorResponse),
+        (status = 502, description = "Ethereum node has failed", body = ErrorResponse),
+        (status = 504, description = "Ethereum node hasn't responded in time", body = ErrorResponse),
+    )
+)]
+#[instrument(
+    name = "Get user profile endpoint handler",
+    err(Debug),
+    skip(ens, db_pool)
+)]
+pub async fn get_user(
+    Path(user_id): Path<String>,
+    State(ens): State<EnsResolver>,
+    State(db_pool): State<PgPool>,
+) -> Result<impl IntoResponse, UserError> {
+    let user_id = if user_id.contains('.') {
+        ens.address_of(&user_id)
+            .await?
+            .ok_or(UserError::UserNotFound)?
+    } else {
+        parse_user_id(&user_id)?.to_hex()
+    };
+    let mut profile = get_wallet_profile_db(&user_id, &db_pool)
+        .await
+        .wrap_err("Failed to get profile")?
+        .ok_or(UserError::UserNotFound)?;
+    // Activity of the account isn't public.
+    profile.last_login_at = None;
+
+    Ok(json_success(profile))
+}
+
+pub(super) fn parse_user_id(user_id: &str) -> Result<Address, UserError> {
+    user_id
+        .parse()
+        .map_err(|e| UserError::Validation(format!("Failed to parse user id: {e}")))
+}
+
+#[instrument(name = "Get profile from database", skip(db_pool))]
+pub(super) async fn get_profile_db(
+    account_id: &Uuid,
+    db_pool: &PgPool,
+) -> Result<Option<Profile>, sqlx::Error> {
+    sqlx::query_as!(
+        ProfileRow,
+        r#"
+        select account_id as "account_id!", nickname, avatar_url, created_at as "created_at!",
+            last_login_at, wallets as "wallets!: SqlJson<Vec<LinkedWallet>>"
+        from profiles
+        where account_id = $1
+        "#,
+        account_id
+    )
+    .fetch_optional(db_pool)
+    .await
+    .map(|row| row.map(Profile::from))
+}
+
+#[instrument(name = "Get profile of wallet from database", skip(db_pool))]
+async fn get_wallet_profile_db(
+    user_id: &str,
+    db_pool: &PgPool,
+) -> Result<Option<Profile>, sqlx::Error> {
+    sqlx::query_as!(
+        ProfileRow,
+        r#"
+        select account_id as "account_id!", nickname, avatar_url, created_at as "created_at!",
+            last_login_at, wallets as "wallets!: SqlJson<Vec<LinkedWallet>>"
+        from profiles
+        where account_id = (select account_id from users where user_id = $1)
+        "#,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await
+    .map(|row| row.map(Profile::from))
+}
+
+#[instrument(name = "Update profile in database", skip(patch, db_pool))]
+async fn update_profile_db(
+    account_id: &Uuid,
+    patch: &ValidatedProfilePatch,
+    db_pool: &PgPool,
+) -> Result<Option<Profile>, sqlx::Error> {
+    sqlx::query_as!(
+        ProfileRow,
+        r#"
+        with updated as (
+            update accounts
+            set nickname = case when $2 then $3 else nickname end,
+                avatar_url = case when $4 then $5 else avatar_url end
+            where account_id = $1
+            returning account_id, nickname, avatar_url, created_at, last_login_at
+        )
+        -- The view doesn't see the update, so only wallets are taken from it.
+        select updated.account_id, updated.nickname, updated.avatar_url, updated.created_at,
+            updated.last_login_at, profiles.wallets as "wallets!: SqlJson<Vec<LinkedWallet>>"
+        from updated join profiles using (account_id)
+        "#,
+        account_id,
+        patch.nickname.is_some(),
+        patch.nickname.as_ref().and_then(Option::as_deref),
+        patch.avatar_url.is_some(),
+        patch
+            .avatar_url
+            .as_ref()
+            .and_then(|url| url.as_ref().map(Url::as_str)),
+    )
+    .fetch_optional(db_pool)
+    .await
+    .map(|row| row.map(Profile::from))
+}
+
+#[instrument(name = "Update last login of account in database", skip(tx))]
+pub(super) async fn update_last_login_db(
+    account_id: &Uuid,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        update accounts set last_login_at = now()
+        where account_id = $1
+        "#,
+        account_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Store user into database", skip(tx))]
+async fn insert_user_db(
+    user_id: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        insert into users(user_id)
+        values ($1)
+        on conflict (user_id) do nothing
+        "#,
+        user_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
 }
 
 #[instrument(name = "Store nonce for address into database", skip(tx))]
-async fn upsert_nonce_for_user_db(
-    user_id: String,
-    nonce: &Uuid,
+async fn insert_nonce_db(
+    user_id: &str,
+    nonce: &Nonce,
     tx: &mut Transaction<'_, Postgres>,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
-        insert into users(user_id, nonce)
-        values ($1, $2)
-        on conflict (user_id)
-        do update set nonce = $2
+        insert into nonces(nonce, user_id, issued_at, expires_at, chain_id, purpose, account_id)
+        values ($1, $2, $3, $4, $5, $6, $7)
         "#,
+        nonce.value,
         user_id,
-        nonce,
+        nonce.issued_at,
+        nonce.expires_at,
+        nonce.chain_id as i64,
+        nonce.purpose.name(),
+        nonce.purpose.account_id(),
     )
     .execute(&mut *tx)
     .await?;
@@ -59,8 +605,144 @@ async fn upsert_nonce_for_user_db(
     Ok(())
 }
 
+/// Get id of the account the wallet is linked to, locking the wallet until the end of transaction.
+#[instrument(name = "Get account of wallet from database", skip(tx))]
+pub(super) async fn get_account_id_db(
+    user_id: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let account_id = sqlx::query_scalar!(
+        r#"
+        select account_id from users
+        where user_id = $1
+        for update
+        "#,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    Ok(account_id.flatten())
+}
+
+/// Create a player account for the wallet, which isn't linked to any account yet.
+#[instrument(name = "Create account in database", skip(tx))]
+pub(super) async fn create_account_db(
+    user_id: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Uuid, sqlx::Error> {
+    let account_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        insert into accounts(account_id)
+        values ($1)
+        "#,
+        account_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    link_wallet_db(user_id, &account_id, tx).await?;
+    grant_role_db(&account_id, Role::Player, tx).await?;
+
+    Ok(account_id)
+}
+
+#[instrument(name = "Link wallet to account in database", skip(tx))]
+pub(super) async fn link_wallet_db(
+    user_id: &str,
+    account_id: &Uuid,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        update users set account_id = $2
+        where user_id = $1
+        "#,
+        user_id,
+        account_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Grant role to account in database", skip(tx))]
+async fn grant_role_db(
+    account_id: &Uuid,
+    role: Role,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        insert into user_roles(account_id, role)
+        values ($1, $2)
+        on conflict (account_id, role) do nothing
+        "#,
+        account_id,
+        role.to_string(),
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Revoke roles of account in database", skip(tx))]
+async fn revoke_roles_db(
+    account_id: &Uuid,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        delete from user_roles
+        where account_id = $1
+        "#,
+        account_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(name = "Get roles of account from database", skip(tx))]
+pub(super) async fn get_account_roles_db(
+    account_id: &Uuid,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<Role>> {
+    let roles = sqlx::query_scalar!(
+        r#"
+        select role from user_roles
+        where account_id = $1
+        order by role
+        "#,
+        account_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    roles
+        .iter()
+        .map(|role| {
+            role.parse()
+                .wrap_err_with(|| format!("Unknown role `{role}`"))
+        })
+        .collect()
+}
+
 #[derive(Error, Debug)]
 pub enum UserError {
+    #[error("Validation error: {0}")]
+    Validation(String),
+    #[error("Chain {0} is not allowed")]
+    ChainNotAllowed(u64),
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Nickname is already taken")]
+    NicknameTaken,
+    #[error(transparent)]
+    Ens(#[from] EnsError),
     #[error("Internal server error: {0}")]
     UnexpectedError(#[from] eyre::Report),
 }
@@ -68,8 +750,28 @@ pub enum UserError {
 impl IntoResponse for UserError {
     fn into_response(self) -> Response {
         let status_code = match self {
+            UserError::Validation(_) | UserError::ChainNotAllowed(_) => StatusCode::BAD_REQUEST,
+            UserError::UserNotFound => StatusCode::NOT_FOUND,
+            UserError::NicknameTaken => StatusCode::CONFLICT,
+            UserError::Ens(EnsError::Ethereum(EthereumError::Timeout)) => {
+                StatusCode::GATEWAY_TIMEOUT
+            }
+            UserError::Ens(EnsError::Ethereum(EthereumError::Request(_))) => {
+                StatusCode::BAD_GATEWAY
+            }
+            UserError::Ens(EnsError::Database(_)) => StatusCode::INTERNAL_SERVER_ERROR,
             UserError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
-        (status_code, json_error(self)).into_response()
+        // Failures of Ethereum node and database are logged, clients get only the status.
+        let error = if status_code.is_server_error() {
+            error!("{self:?}");
+            status_code
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_owned()
+        } else {
+            self.to_string()
+        };
+        (status_code, json_error(error)).into_response()
     }
 }
@@ -0,0 +1,304 @@
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use ethers::prelude::Address;
+use eyre::{Report, WrapErr};
+use sqlx::{PgPool, Postgres, Transaction};
+use thiserror::Error;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    address::ToHex,
+    config::{ChainsConfig, Eip712Config, NonceConfig, SiweConfig},
+    ethereum::Ethereum,
+    metrics::Metrics,
+    nonce::NoncePurpose,
+    revocation::RevocationList,
+    routes::{
+        consume_nonce_db, get_account_id_db, get_profile_db, issue_nonce, json_error, json_success,
+        link_wallet_db, revoke_refresh_tokens_of_wallet_db, verify_wallet, AuthError, AuthEvent,
+        AuthEventKind, AuthRejection, Challenge, ErrorResponse, JsonResponse, NonceQuery, Payload,
+        Profile, RequestMeta, User, UserError,
+    },
+};
+
+/// Issue the challenge, which the wallet signs to be linked to the account of the
+/// authenticated user.
+///
+/// Unlike the sign-in challenge, it names the account, so signatures made to sign in
+/// can't be used to link the wallet.
+#[utoipa::path(
+    get,
+    path = "/me/wallets/{user_id}/nonce",
+    tag = "wallets",
+    params(("user_id" = String, Path, description = "Address of the wallet to link"), NonceQuery),
+    responses(
+        AuthRejection,
+        (status = 200, description = "Challenge to sign", body = JsonResponse<Challenge>),
+        (status = 400, description = "Invalid address or chain", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+#[instrument(
+    name = "Set link nonce endpoint handler",
+    err(Debug),
+    skip(
+        user,
+        meta,
+        siwe_config,
+        eip712_config,
+        chains_config,
+        nonce_config,
+        metrics,
+        db_pool
+    ),
+    fields(account_id = %user.id)
+)]
+pub async fn set_link_nonce(
+    user: User,
+    meta: RequestMeta,
+    Path(user_id): Path<String>,
+    Query(NonceQuery { mode, chain_id }): Query<NonceQuery>,
+    State(siwe_config): State<SiweConfig>,
+    State(eip712_config): State<Eip712Config>,
+    State(chains_config): State<ChainsConfig>,
+    State(nonce_config): State<NonceConfig>,
+    State(metrics): State<Metrics>,
+    State(db_pool): State<PgPool>,
+) -> Result<impl IntoResponse, UserError> {
+    let mut event = AuthEvent::of_user(AuthEventKind::NonceIssued, meta, &user);
+    let result = issue_nonce(
+        &mut event,
+        &user_id,
+        NoncePurpose::LinkWallet {
+            account_id: user.id,
+        },
+        mode,
+        chain_id,
+        &siwe_config,
+        &eip712_config,
+        &chains_config,
+        &nonce_config,
+        &db_pool,
+    )
+    .await;
+    if result.is_ok() {
+        metrics.observe_nonce_issued();
+    }
+    event.record(&result, &db_pool).await;
+
+    result.map(json_success)
+}
+
+/// Link the wallet, which has signed the link challenge, to the account of the authenticated user.
+#[utoipa::path(
+    post,
+    path = "/me/wallets",
+    tag = "wallets",
+    request_body = Payload,
+    responses(
+        AuthRejection,
+        (status = 200, description = "Profile with the linked wallet", body = JsonResponse<Profile>),
+        (status = 401, description = "Invalid signature or challenge isn't issued for linking", body = ErrorResponse),
+        (status = 409, description = "Wallet is already linked", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+#[instrument(name = "Link wallet", skip_all, fields(account_id = %user.id), err(Debug))]
+pub async fn link_wallet(
+    user: User,
+    State(siwe_config): State<SiweConfig>,
+    State(eip712_config): State<Eip712Config>,
+    State(chains_config): State<ChainsConfig>,
+    State(ethereum): State<Option<Ethereum>>,
+    State(db_pool): State<PgPool>,
+    Json(payload): Json<Payload>,
+) -> Result<impl IntoResponse, WalletError> {
+    let wallet = verify_wallet(
+        payload,
+        NoncePurpose::LinkWallet {
+            account_id: user.id,
+        },
+        &siwe_config,
+        &eip712_config,
+        &chains_config,
+        ethereum.as_ref(),
+        &db_pool,
+    )
+    .await?;
+
+    let mut tx = db_pool
+        .begin()
+        .await
+        .wrap_err("Failed to start sql transaction")?;
+    let consumed = consume_nonce_db(&wallet.nonce, &wallet.user_id, &mut tx)
+        .await
+        .wrap_err("Failed to consume nonce")?;
+    if !consumed {
+        return Err(AuthError::ConsumedNonce.into());
+    }
+
+    let account_id = get_account_id_db(&wallet.user_id, &mut tx)
+        .await
+        .wrap_err("Failed to get account of wallet")?;
+    match account_id {
+        Some(account_id) if account_id == user.id => return Err(WalletError::AlreadyLinked),
+        Some(_) => return Err(WalletError::LinkedToAnotherAccount),
+        None => link_wallet_db(&wallet.user_id, &user.id, &mut tx)
+            .await
+            .wrap_err("Failed to link wallet")?,
+    }
+    tx.commit()
+        .await
+        .wrap_err("Failed to commit sql transaction")?;
+
+    let profile = get_profile_db(&user.id, &db_pool)
+        .await
+        .wrap_err("Failed to get profile")?
+        .ok_or_else(|| WalletError::Unexpected(eyre::eyre!("Account is absent")))?;
+
+    Ok(json_success(profile))
+}
+
+/// Unlink the wallet from the account of the authenticated user and revoke its tokens.
+///
+/// The last wallet can't be unlinked, otherwise nobody would be able to sign in to the account.
+#[utoipa::path(
+    delete,
+    path = "/me/wallets/{user_id}",
+    tag = "wallets",
+    params(("user_id" = String, Path, description = "Address of the wallet")),
+    responses(
+        AuthRejection,
+        (status = 200, description = "Profile without the wallet", body = JsonResponse<Profile>),
+        (status = 404, description = "Wallet isn't linked to the account", body = ErrorResponse),
+        (status = 409, description = "Wallet is the last one", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("cookie" = []))
+)]
+#[instrument(
+    name = "Unlink wallet",
+    skip(user, revocation_list, db_pool),
+    fields(account_id = %user.id),
+    err(Debug)
+)]
+pub async fn unlink_wallet(
+    user: User,
+    Path(user_id): Path<String>,
+    State(revocation_list): State<RevocationList>,
+    State(db_pool): State<PgPool>,
+) -> Result<impl IntoResponse, WalletError> {
+    let user_id = user_id
+        .parse::<Address>()
+        .map_err(|e| WalletError::Validation(format!("Failed to parse user id: {e}")))?
+        .to_hex();
+    let mut tx = db_pool
+        .begin()
+        .await
+        .wrap_err("Failed to start sql transaction")?;
+
+    let wallets = get_wallets_of_account_db(&user.id, &mut tx)
+        .await
+        .wrap_err("Failed to get wallets of account")?;
+    if !wallets.contains(&user_id) {
+        return Err(WalletError::NotLinked);
+    }
+    if wallets.len() == 1 {
+        return Err(WalletError::LastWallet);
+    }
+
+    unlink_wallet_db(&user_id, &mut tx)
+        .await
+        .wrap_err("Failed to unlink wallet")?;
+    revoke_refresh_tokens_of_wallet_db(&user_id, &user.id, &mut tx)
+        .await
+        .wrap_err("Failed to revoke refresh tokens of wallet")?;
+    tx.commit()
+        .await
+        .wrap_err("Failed to commit sql transaction")?;
+    revocation_list
+        .revoke_tokens_of_wallet(&user_id, &user.id)
+        .await
+        .wrap_err("Failed to revoke access tokens of wallet")?;
+
+    let profile = get_profile_db(&user.id, &db_pool)
+        .await
+        .wrap_err("Failed to get profile")?
+        .ok_or_else(|| WalletError::Unexpected(eyre::eyre!("Account is absent")))?;
+
+    Ok(json_success(profile))
+}
+
+/// Get addresses of the wallets linked to the account, locking them until the end of transaction.
+#[instrument(name = "Get wallets of account from database", skip(tx))]
+async fn get_wallets_of_account_db(
+    account_id: &Uuid,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        select user_id from users
+        where account_id = $1
+        for update
+        "#,
+        account_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+}
+
+#[instrument(name = "Unlink wallet from account in database", skip(tx))]
+async fn unlink_wallet_db(
+    user_id: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        update users set account_id = null
+        where user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum WalletError {
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[error("Validation error: {0}")]
+    Validation(String),
+    #[error("Wallet is already linked to the account")]
+    AlreadyLinked,
+    #[error("Wallet is linked to another account")]
+    LinkedToAnotherAccount,
+    #[error("Wallet isn't linked to the account")]
+    NotLinked,
+    #[error("The last wallet of the account can't be unlinked")]
+    LastWallet,
+    #[error("Unexpected error: {0}")]
+    Unexpected(#[from] Report),
+}
+
+impl IntoResponse for WalletError {
+    fn into_response(self) -> Response {
+        let status_code = match self {
+            WalletError::Auth(e) => return e.into_response(),
+            WalletError::Validation(_) => StatusCode::BAD_REQUEST,
+            WalletError::AlreadyLinked
+            | WalletError::LinkedToAnotherAccount
+            | WalletError::LastWallet => StatusCode::CONFLICT,
+            WalletError::NotLinked => StatusCode::NOT_FOUND,
+            WalletError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status_code, json_error(self.to_string())).into_response()
+    }
+}
@@ -0,0 +1,86 @@
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Json},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::jwt::Jwt;
+
+/// Clients are allowed to cache keys and discovery document for this amount of seconds.
+const CACHE_MAX_AGE_SECS: u32 = 3600;
+
+/// OpenID Provider Metadata, see
+/// <https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata>.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub jwks_uri: String,
+    pub token_endpoint: String,
+    pub revocation_endpoint: String,
+    pub response_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
+
+impl OpenIdConfiguration {
+    pub fn new(issuer: &str) -> Self {
+        Self {
+            issuer: issuer.to_owned(),
+            jwks_uri: format!("{issuer}/.well-known/jwks.json"),
+            token_endpoint: format!("{issuer}/token/refresh"),
+            revocation_endpoint: format!("{issuer}/tokens/revoke"),
+            response_types_supported: vec!["token".to_owned()],
+            subject_types_supported: vec!["public".to_owned()],
+            id_token_signing_alg_values_supported: vec!["EdDSA".to_owned()],
+            claims_supported: [
+                "iss",
+                "sub",
+                "exp",
+                "iat",
+                "jti",
+                "wallet",
+                "ens",
+                "chain_id",
+                "roles",
+                "collections",
+            ]
+            .map(ToOwned::to_owned)
+            .to_vec(),
+        }
+    }
+}
+
+/// Public keys, which verify signatures of access tokens.
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "well-known",
+    responses((status = 200, description = "JSON Web Key Set", body = Object))
+)]
+pub async fn jwks(State(jwt): State<Jwt>) -> impl IntoResponse {
+    (cache_control(), Json(jwt.jwk_set().clone()))
+}
+
+/// Discovery document of the token issuer.
+#[utoipa::path(
+    get,
+    path = "/.well-known/openid-configuration",
+    tag = "well-known",
+    responses((status = 200, body = OpenIdConfiguration))
+)]
+pub async fn openid_configuration(State(jwt): State<Jwt>) -> impl IntoResponse {
+    (
+        cache_control(),
+        Json(OpenIdConfiguration::new(jwt.issuer())),
+    )
+}
+
+fn cache_control() -> [(header::HeaderName, String); 1] {
+    [(
+        header::CACHE_CONTROL,
+        format!("public, max-age={CACHE_MAX_AGE_SECS}"),
+    )]
+}
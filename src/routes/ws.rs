@@ -0,0 +1,256 @@
+use std::borrow::Cow;
+
+use axum::{
+    extract::{
+        rejection::{TypedHeaderRejection, TypedHeaderRejectionReason},
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    headers::{authorization::Bearer, Authorization},
+    response::Response,
+    TypedHeader,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::broadcast::error::RecvError,
+    time::{Instant, MissedTickBehavior},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::WebSocketConfig,
+    events::{Event, EventBus},
+    jwt::{Claims, Jwt},
+    revocation::{token_deadline, RevocationList},
+    routes::{verify_token, AuthError, ErrorResponse},
+};
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// Token of the connection, if it hasn't been passed along with the upgrade request.
+    Authenticate {
+        token: String,
+    },
+    Ping,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Authenticated {
+        account_id: Uuid,
+        expires_at: DateTime<Utc>,
+    },
+    Pong,
+}
+
+/// Upgrade to WebSocket, which pushes events concerning the authenticated user.
+///
+/// The token is taken from the `Authorization` header of the upgrade request or, since
+/// browsers can't set headers of the handshake, from the first message
+/// `{"type": "authenticate"}`. Cookies aren't accepted: browsers send them along with
+/// cross-site handshakes, which CORS doesn't restrict.
+/// The socket is closed once the token expires or is revoked, or the server shuts down.
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "events",
+    responses(
+        (status = 101, description = "Switched to WebSocket, which pushes `Event` messages"),
+        (status = 400, description = "Invalid access token", body = ErrorResponse),
+        (status = 401, description = "Access token is expired or revoked", body = ErrorResponse),
+    ),
+    security((), ("bearer" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+#[instrument(name = "WebSocket upgrade", skip_all, err(Debug))]
+pub async fn ws(
+    upgrade: WebSocketUpgrade,
+    bearer: Result<TypedHeader<Authorization<Bearer>>, TypedHeaderRejection>,
+    State(jwt): State<Jwt>,
+    State(revocation_list): State<RevocationList>,
+    State(events): State<EventBus>,
+    State(config): State<WebSocketConfig>,
+    State(shutdown): State<CancellationToken>,
+) -> Result<Response, AuthError> {
+    let claims = match bearer {
+        Ok(TypedHeader(bearer)) => {
+            Some(verify_token(bearer.token(), &jwt, &revocation_list).await?)
+        }
+        Err(e) if matches!(e.reason(), TypedHeaderRejectionReason::Missing) => None,
+        Err(_) => return Err(AuthError::InvalidAuthToken),
+    };
+    let session = Session {
+        jwt,
+        revocation_list,
+        events,
+        config,
+        shutdown,
+    };
+
+    Ok(upgrade.on_upgrade(move |socket| session.serve(socket, claims)))
+}
+
+struct Session {
+    jwt: Jwt,
+    revocation_list: RevocationList,
+    events: EventBus,
+    config: WebSocketConfig,
+    /// Cancelled on shutdown, which closes the socket.
+    shutdown: CancellationToken,
+}
+
+impl Session {
+    async fn serve(self, mut socket: WebSocket, claims: Option<Claims>) {
+        // Events published during authentication aren't missed.
+        let mut events = self.events.subscribe();
+        let claims = match claims {
+            Some(claims) => claims,
+            None => match self.authenticate(&mut socket).await {
+                Ok(claims) => claims,
+                Err(reason) => {
+                    close(socket, close_code::POLICY, reason).await;
+                    return;
+                }
+            },
+        };
+        let authenticated = ServerMessage::Authenticated {
+            account_id: claims.sub,
+            expires_at: claims.expires_at(),
+        };
+        if send_json(&mut socket, &authenticated).await.is_err() {
+            return;
+        }
+
+        if let Some((code, reason)) = self.run(&mut socket, &claims, &mut events).await {
+            close(socket, code, reason).await;
+        }
+    }
+
+    /// Wait for the first message, which has to carry the token.
+    #[instrument(name = "WebSocket authentication", skip_all)]
+    async fn authenticate(&self, socket: &mut WebSocket) -> Result<Claims, Cow<'static, str>> {
+        let message = tokio::time::timeout(self.config.auth_timeout(), socket.recv())
+            .await
+            .map_err(|_| "Token hasn't been sent in time")?;
+        let Some(Ok(Message::Text(text))) = message else {
+            return Err("First message has to contain the token".into());
+        };
+        let Ok(ClientMessage::Authenticate { token }) = serde_json::from_str(&text) else {
+            return Err("First message has to contain the token".into());
+        };
+
+        verify_token(&token, &self.jwt, &self.revocation_list)
+            .await
+            .map_err(|e| match e {
+                AuthError::Unexpected(e) => {
+                    error!("Failed to verify token of WebSocket: {e:?}");
+
+                    "Unexpected error".into()
+                }
+                e => e.to_string().into(),
+            })
+    }
+
+    /// Push events to the client until the session ends.
+    ///
+    /// Returns the reason to close the socket with, if the client is still there.
+    #[instrument(name = "WebSocket session", skip_all, fields(account_id = %claims.sub))]
+    async fn run(
+        &self,
+        socket: &mut WebSocket,
+        claims: &Claims,
+        events: &mut tokio::sync::broadcast::Receiver<Event>,
+    ) -> Option<(u16, Cow<'static, str>)> {
+        let heartbeat_interval = self.config.heartbeat_interval();
+        let mut heartbeat =
+            tokio::time::interval_at(Instant::now() + heartbeat_interval, heartbeat_interval);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last_seen = Instant::now();
+        let expiry = tokio::time::sleep_until(Instant::from_std(token_deadline(claims)));
+        tokio::pin!(expiry);
+        info!("WebSocket session has started");
+
+        loop {
+            tokio::select! {
+                message = socket.recv() => {
+                    let message = match message {
+                        Some(Ok(message)) => message,
+                        Some(Err(e)) => {
+                            debug!("WebSocket connection has failed: {e}");
+                            return None;
+                        }
+                        None => return None,
+                    };
+                    last_seen = Instant::now();
+                    match message {
+                        Message::Text(text) => match serde_json::from_str(&text) {
+                            Ok(ClientMessage::Ping) => {
+                                send_json(socket, &ServerMessage::Pong).await.ok()?;
+                            }
+                            Ok(ClientMessage::Authenticate { .. }) => {
+                                debug!("Ignoring authentication of authenticated session");
+                            }
+                            Err(e) => debug!("Ignoring malformed message: {e}"),
+                        },
+                        Message::Close(_) => return None,
+                        // Pings are answered by the socket itself.
+                        Message::Binary(_) | Message::Ping(_) | Message::Pong(_) => {}
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(event) if event.concerns(claims) => {
+                        send_json(socket, &event).await.ok()?;
+                        if matches!(event, Event::SessionRevoked { jti, .. } if jti == claims.jti) {
+                            return Some((close_code::POLICY, "Session has been revoked".into()));
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("WebSocket session has missed {missed} events");
+                    }
+                    // The bus lives in the state of the app, so it's closed only along with it.
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() >= 2 * heartbeat_interval {
+                        debug!("WebSocket client hasn't answered pings, disconnecting");
+                        return None;
+                    }
+                    // Revocations made by other instances aren't published on the bus.
+                    match self.revocation_list.is_revoked(claims).await {
+                        Ok(true) => {
+                            return Some((close_code::POLICY, "Session has been revoked".into()));
+                        }
+                        Ok(false) => {}
+                        Err(e) => warn!("Failed to check revocation of WebSocket token: {e}"),
+                    }
+                    socket.send(Message::Ping(Vec::new())).await.ok()?;
+                }
+                _ = &mut expiry => {
+                    return Some((close_code::POLICY, "Token has expired".into()));
+                }
+                _ = self.shutdown.cancelled() => {
+                    return Some((close_code::AWAY, "Server is shutting down".into()));
+                }
+            }
+        }
+    }
+}
+
+async fn send_json<T: Serialize>(socket: &mut WebSocket, message: &T) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).map_err(axum::Error::new)?;
+
+    socket.send(Message::Text(text)).await
+}
+
+async fn close(mut socket: WebSocket, code: u16, reason: Cow<'static, str>) {
+    let frame = CloseFrame { code, reason };
+    if let Err(e) = socket.send(Message::Close(Some(frame))).await {
+        debug!("Failed to close WebSocket: {e}");
+    }
+}
@@ -1,30 +1,63 @@
-use std::net::TcpListener;
+use std::{
+    future::Future,
+    net::{SocketAddr, TcpListener},
+    time::Duration,
+};
 
-use axum::{routing::IntoMakeService, Router};
-use eyre::{Result, WrapErr};
+use axum::{extract::connect_info::IntoMakeServiceWithConnectInfo, Router};
+use eyre::{eyre, Result, WrapErr};
 use hyper::{server::conn::AddrIncoming, Server};
-use sqlx::{postgres::PgPoolOptions, PgPool};
-use tracing::{info, instrument};
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions, PgPool};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument, warn};
 
 use crate::{
     config::{DatabaseConfig, MainConfig},
-    jwt::Jwt,
-    routes::{setup_router, SharedState},
+    cors::cors_layer,
+    ens::EnsResolver,
+    ethereum::Ethereum,
+    events::EventBus,
+    indexer::spawn_indexer,
+    metrics::Metrics,
+    nft::NftInspector,
+    nonce::spawn_nonce_sweeper,
+    rate_limit::RateLimiter,
+    revocation::RevocationList,
+    routes::{setup_metrics_router, setup_router, SharedState},
 };
 
-type HyperServer = Server<AddrIncoming, IntoMakeService<Router>>;
+/// Migrations embedded into the binary, which are applied at start.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Upper bound of waiting for connections, which are still used by abandoned requests, on shutdown.
+const POOL_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximal number of connections of the database pool.
+pub const MAX_DB_CONNECTIONS: u32 = 10;
+
+type HyperServer = Server<AddrIncoming, IntoMakeServiceWithConnectInfo<Router, SocketAddr>>;
 
 #[derive(Debug)]
 pub struct App {
     pub server: HyperServer,
     pub port: u16,
+    /// Server of the admin port, if metrics are served separately.
+    pub admin_server: Option<HyperServer>,
+    pub admin_port: Option<u16>,
+    /// Cancelled on shutdown signal, which stops the servers and the background tasks.
+    shutdown: CancellationToken,
+    drain_timeout: Duration,
+    /// Background tasks, which are awaited before the database pool is closed.
+    tasks: Vec<JoinHandle<()>>,
+    db_pool: PgPool,
 }
 
 impl App {
     #[instrument(name = "Building Application", skip_all)]
     pub async fn build(config: MainConfig) -> Result<Self> {
         let db_pool = setup_db_pool(&config.db);
-        sqlx::migrate!()
+        MIGRATOR
             .run(&db_pool)
             .await
             .wrap_err("Failed to run migrations")?;
@@ -34,37 +67,230 @@ impl App {
         let listener =
             TcpListener::bind(&app_address).wrap_err("Failed to bind address for app")?;
         let port = listener.local_addr()?.port();
+        let admin_listener = config
+            .metrics
+            .admin_port
+            .map(|admin_port| {
+                let admin_address = format!("{}:{admin_port}", config.app.host);
+                info!("Binding address - {admin_address} for admin");
+                TcpListener::bind(&admin_address).wrap_err("Failed to bind address for admin")
+            })
+            .transpose()?;
+        let admin_port = admin_listener
+            .as_ref()
+            .map(|listener| listener.local_addr().map(|address| address.port()))
+            .transpose()?;
         let jwt = config
             .secrets
-            .jwt()
+            .jwt(&config.app.base_url)
             .wrap_err("Failed to compose jwt tools")?;
-        let server = setup_server(listener, db_pool, jwt)?;
-        Ok(Self { server, port })
+        let shutdown = CancellationToken::new();
+        let mut tasks = vec![spawn_nonce_sweeper(
+            db_pool.clone(),
+            config.nonce.sweep_interval(),
+            shutdown.clone(),
+        )];
+        let events = EventBus::new();
+        let revocation_list = RevocationList::new(
+            db_pool.clone(),
+            config.revocation.cache_ttl(),
+            events.clone(),
+        );
+        let ethereum = config
+            .ethereum
+            .as_ref()
+            .map(Ethereum::new)
+            .transpose()
+            .wrap_err("Failed to setup Ethereum client")?;
+        if let Some(indexer_config) = config.indexer {
+            let ethereum = ethereum
+                .clone()
+                .ok_or_else(|| eyre!("Indexer requires Ethereum node to be configured"))?;
+            tasks.push(spawn_indexer(
+                ethereum,
+                db_pool.clone(),
+                indexer_config,
+                events.clone(),
+                shutdown.clone(),
+            ));
+        }
+        let ens = EnsResolver::new(ethereum.clone(), db_pool.clone(), config.ens.cache_ttl());
+        let nft = NftInspector::new(config.nft, ethereum.clone())
+            .wrap_err("Failed to setup NFT inspector")?;
+        let cors = cors_layer(&config.cors).wrap_err("Failed to setup CORS policy")?;
+        let metrics = Metrics::new(MAX_DB_CONNECTIONS).wrap_err("Failed to setup metrics")?;
+        let state = SharedState {
+            ens,
+            nft,
+            ethereum,
+            revocation_list,
+            rate_limiter: RateLimiter::new(config.rate_limit),
+            metrics,
+            migrator: &MIGRATOR,
+            db_pool: db_pool.clone(),
+            jwt,
+            siwe: config.siwe,
+            eip712: config.eip712,
+            chains: config.chains,
+            nonce: config.nonce,
+            refresh_token: config.refresh_token,
+            session: config.session,
+            websocket: config.websocket,
+            openapi: config.openapi,
+            events,
+            shutdown: shutdown.clone(),
+        };
+        let (server, admin_server) = match admin_listener {
+            Some(admin_listener) => (
+                setup_server(listener, setup_router(state.clone(), cors))?,
+                Some(setup_server(admin_listener, setup_metrics_router(state))?),
+            ),
+            None => {
+                let router = setup_router(state.clone(), cors).merge(setup_metrics_router(state));
+                (setup_server(listener, router)?, None)
+            }
+        };
+        Ok(Self {
+            server,
+            port,
+            admin_server,
+            admin_port,
+            shutdown,
+            drain_timeout: config.app.drain_timeout(),
+            tasks,
+            db_pool,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
 
+    pub fn admin_port(&self) -> Option<u16> {
+        self.admin_port
+    }
+
+    /// Token, which stops the app the same way as a shutdown signal when cancelled.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Serve requests until SIGTERM or SIGINT is received.
+    ///
+    /// On shutdown new connections aren't accepted anymore, while in-flight requests
+    /// are given `drain_timeout` to complete. Then background tasks are stopped
+    /// and the database pool is closed.
     #[tracing::instrument(name = "Starting application", skip_all)]
     pub async fn run_until_stopped(self) -> Result<()> {
-        self.server.await.wrap_err("Failed to run server")
+        tokio::spawn(cancel_on_signal(self.shutdown.clone()));
+        let server = graceful(self.server, &self.shutdown);
+        let servers = async {
+            match self.admin_server {
+                Some(admin_server) => {
+                    let admin_server = graceful(admin_server, &self.shutdown);
+                    tokio::try_join!(
+                        async { server.await.wrap_err("Failed to run server") },
+                        async { admin_server.await.wrap_err("Failed to run admin server") },
+                    )?;
+
+                    Ok(())
+                }
+                None => server.await.wrap_err("Failed to run server"),
+            }
+        };
+        tokio::pin!(servers);
+
+        let result = tokio::select! {
+            result = &mut servers => result,
+            _ = self.shutdown.cancelled() => {
+                info!("Draining in-flight requests");
+                match tokio::time::timeout(self.drain_timeout, &mut servers).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        warn!(
+                            "Requests haven't completed in {:?}, stopping anyway",
+                            self.drain_timeout
+                        );
+                        Ok(())
+                    }
+                }
+            }
+        };
+
+        // Background tasks are stopped even if the server has failed.
+        self.shutdown.cancel();
+        for task in self.tasks {
+            if let Err(e) = task.await {
+                error!("Background task has failed: {e}");
+            }
+        }
+        if tokio::time::timeout(POOL_CLOSE_TIMEOUT, self.db_pool.close())
+            .await
+            .is_err()
+        {
+            warn!("Database connections haven't been returned in {POOL_CLOSE_TIMEOUT:?}");
+        }
+        info!("Application has stopped");
+
+        result
     }
 }
 
+/// Stop accepting connections once `shutdown` is cancelled, letting in-flight requests complete.
+fn graceful(
+    server: HyperServer,
+    shutdown: &CancellationToken,
+) -> impl Future<Output = hyper::Result<()>> {
+    let shutdown = shutdown.clone();
+
+    server.with_graceful_shutdown(async move { shutdown.cancelled().await })
+}
+
+/// Cancel `shutdown` on SIGTERM or SIGINT.
+async fn cancel_on_signal(shutdown: CancellationToken) {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for SIGINT: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => info!("Received SIGINT, shutting down"),
+        _ = terminate => info!("Received SIGTERM, shutting down"),
+        _ = shutdown.cancelled() => return,
+    }
+    shutdown.cancel();
+}
+
 #[instrument(name = "Setup database pool", skip_all)]
 pub fn setup_db_pool(config: &DatabaseConfig) -> PgPool {
     PgPoolOptions::new()
+        .max_connections(MAX_DB_CONNECTIONS)
         .acquire_timeout(std::time::Duration::from_secs(2))
         .connect_lazy_with(config.with_db())
 }
 
 #[tracing::instrument(name = "Setup server", skip_all)]
-pub fn setup_server(listener: TcpListener, db_pool: PgPool, jwt: Jwt) -> Result<HyperServer> {
-    let state = SharedState { db_pool, jwt };
-
-    let router = setup_router(state);
-    let server = axum::Server::from_tcp(listener)?.serve(router.into_make_service());
+pub fn setup_server(listener: TcpListener, router: Router) -> Result<HyperServer> {
+    let server = axum::Server::from_tcp(listener)?
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>());
 
     Ok(server)
 }
@@ -1,20 +1,35 @@
 use eyre::{Result, WrapErr};
+use opentelemetry::{
+    global,
+    sdk::{
+        propagation::TraceContextPropagator,
+        trace::{self, Sampler, Tracer},
+        Resource,
+    },
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
 use tokio::task::JoinHandle;
 use tracing::{subscriber, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
 use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
 
+use crate::config::OtlpConfig;
+
 /// Compose multiple layers into a `tracing`'s subscriber.
 ///
 /// # Implementation Notes
 ///
 /// We are using `impl Subscriber` as return type to avoid having to spell out the actual
 /// type of the returned subscriber, which is indeed quite complex.
+///
+/// Spans are also exported with the `tracer`, if it's set.
 pub fn build_subscriber<Sink>(
     name: String,
     env_filter: String,
     sink: Sink,
+    tracer: Option<Tracer>,
 ) -> Box<dyn Subscriber + Send + Sync>
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
@@ -26,10 +41,37 @@ where
         Registry::default()
             .with(env_filter)
             .with(JsonStorageLayer)
-            .with(formatting_layer),
+            .with(formatting_layer)
+            .with(tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer))),
     )
 }
 
+/// Compose tracer, which exports spans to OpenTelemetry collector in batches.
+///
+/// W3C trace context propagation is enabled as well, so requests continue traces of callers.
+pub fn otlp_tracer(config: &OtlpConfig) -> Result<Tracer> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sampling_ratio)));
+    // HTTP exporter posts to the endpoint as is, unlike gRPC one.
+    let endpoint = format!("{}/v1/traces", config.endpoint.trim_end_matches('/'));
+    let resource = Resource::new([KeyValue::new("service.name", config.service_name.clone())]);
+
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config()
+                .with_sampler(sampler)
+                .with_resource(resource),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)
+        .wrap_err("Failed to install OTLP pipeline")
+}
+
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) -> Result<()> {
     LogTracer::init().wrap_err("Failed to set logger")?;
     subscriber::set_global_default(subscriber).wrap_err("Failed to set subscriber")?;
@@ -0,0 +1,215 @@
+mod helpers;
+
+use ethers::prelude::{rand, LocalWallet, Signer};
+use eyre::{bail, Result, WrapErr};
+use helpers::{jwt_of, spawn_app, spawn_app_with_config, TestApp};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use battlemon_ethereum::{
+    address::ToHex,
+    role::Role,
+    routes::{AuthEventRecord, AuthEventsPage, JsonResponse},
+};
+
+/// Sign in as a user with admin role.
+async fn login_as_admin(app: &TestApp) -> Result<Value> {
+    app.login().await?;
+    sqlx::query!(
+        r#"
+        insert into user_roles(account_id, role)
+        select account_id, $2 from users
+        where user_id = $1
+        "#,
+        app.user_address(),
+        Role::Admin.to_string()
+    )
+    .execute(&app.db_pool)
+    .await
+    .wrap_err("Failed to grant role")?;
+
+    app.login().await
+}
+
+async fn events_of(app: &TestApp, user_id: &str) -> Result<Vec<AuthEventRecord>> {
+    let events = sqlx::query_as!(
+        AuthEventRecord,
+        r#"
+        select id, kind, outcome, failure_reason, account_id, user_id, ip, user_agent, request_id, created_at
+        from auth_events
+        where user_id = $1
+        order by id
+        "#,
+        user_id
+    )
+    .fetch_all(&app.db_pool)
+    .await?;
+
+    Ok(events)
+}
+
+async fn page_of(response: reqwest::Response) -> Result<AuthEventsPage> {
+    let JsonResponse::Success(page) = response.json().await? else {
+        bail!("Expected success response");
+    };
+
+    Ok(page)
+}
+
+#[tokio::test]
+async fn sign_in_is_recorded() -> Result<()> {
+    let app = spawn_app().await;
+
+    app.login().await?;
+
+    let events = events_of(&app, &app.user_address()).await?;
+    let kinds: Vec<_> = events.iter().map(|event| event.kind.as_str()).collect();
+    assert_eq!(vec!["nonce_issued", "sign_in"], kinds);
+    let sign_in = &events[1];
+    assert_eq!("success", sign_in.outcome);
+    assert!(sign_in.account_id.is_some());
+    assert_eq!(Some("127.0.0.1"), sign_in.ip.as_deref());
+    assert!(sign_in.request_id.is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_sign_in_is_recorded_with_reason() -> Result<()> {
+    let app = spawn_app().await;
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign("not the challenge").await?;
+
+    let response = app
+        .web3_auth_response(signature.to_string().as_str(), &challenge.message)
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let events = events_of(&app, &app.user_address()).await?;
+    let sign_in = events.last().unwrap();
+    assert_eq!("sign_in", sign_in.kind);
+    assert_eq!("failure", sign_in.outcome);
+    assert!(sign_in
+        .failure_reason
+        .as_deref()
+        .unwrap()
+        .starts_with("Signature verification error"));
+    assert_eq!(None, sign_in.account_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sign_in_rejected_for_session_mode_is_recorded() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.session.cookies_enabled = false).await;
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign(&challenge.message).await?;
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/web3_auth", app.address))
+        .json(&json!({
+            "signature": signature.to_string(),
+            "message": challenge.message,
+            "session": "cookie",
+        }))
+        .send()
+        .await?;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let events = events_of(&app, &app.user_address()).await?;
+    let sign_in = events.last().unwrap();
+    assert_eq!("sign_in", sign_in.kind);
+    assert_eq!("failure", sign_in.outcome);
+    assert_eq!(
+        Some("Validation error: Cookie sessions are disabled"),
+        sign_in.failure_reason.as_deref()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn refresh_and_logout_are_recorded() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let refresh_token = tokens["refresh_token"].as_str().unwrap();
+
+    let response = app.refresh_token_response(refresh_token).await?;
+    assert_eq!(StatusCode::OK, response.status());
+    let response = app
+        .post_with_bearer::<()>("logout", jwt_of(&tokens), None)
+        .await?;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let events = events_of(&app, &app.user_address()).await?;
+    let kinds: Vec<_> = events.iter().map(|event| event.kind.as_str()).collect();
+    assert_eq!(
+        vec!["nonce_issued", "sign_in", "token_refresh", "logout"],
+        kinds
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_queries_events_of_address_page_by_page() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = login_as_admin(&app).await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    app.login_with(&wallet).await?;
+    let user_id = wallet.address().to_hex();
+
+    let response = app
+        .get_with_bearer(
+            &format!("auth_events?user_id={user_id}&limit=1"),
+            jwt_of(&tokens),
+        )
+        .await?;
+    let first_page = page_of(response).await?;
+    let response = app
+        .get_with_bearer(
+            &format!(
+                "auth_events?user_id={user_id}&limit=1&before={}",
+                first_page.next.unwrap()
+            ),
+            jwt_of(&tokens),
+        )
+        .await?;
+    let second_page = page_of(response).await?;
+
+    assert_eq!("sign_in", first_page.events[0].kind);
+    assert_eq!("nonce_issued", second_page.events[0].kind);
+    assert!(first_page
+        .events
+        .iter()
+        .chain(&second_page.events)
+        .all(|event| event.user_id.as_deref() == Some(user_id.as_str())));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn events_are_not_available_to_players() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+
+    let response = app.get_with_bearer("auth_events", jwt_of(&tokens)).await?;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn events_can_not_be_changed() -> Result<()> {
+    let app = spawn_app().await;
+    app.login().await?;
+
+    let result = sqlx::query!("delete from auth_events")
+        .execute(&app.db_pool)
+        .await;
+
+    assert!(result.is_err());
+
+    Ok(())
+}
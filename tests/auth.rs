@@ -1,9 +1,16 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{decode_claims, spawn_app, spawn_app_with_config, spawn_mock_rpc, TestApp};
 use base64::Engine;
-use battlemon_ethereum::jwt::Claims;
+use battlemon_ethereum::{
+    address::ToHex, config::EthereumConfig, jwt::Claims, routes::JsonResponse,
+};
+use ethers::prelude::{rand, LocalWallet, Signer};
 use eyre::Result;
 use jsonwebtoken::{Algorithm, DecodingKey};
-use uuid::Uuid;
+use reqwest::StatusCode;
+use rstest::rstest;
+use serde_json::{json, Value};
+use siwe::Message;
+use std::{collections::HashMap, time::Duration};
 
 mod helpers;
 
@@ -11,11 +18,11 @@ mod helpers;
 async fn web3_auth_works_correctly() -> Result<()> {
     let app = spawn_app().await;
     let user_address = app.user_address();
-    let nonce: Uuid = app.get_nonce_for_user(&user_address).await?;
-    let signature = app.sign(nonce.to_string().as_str()).await?;
+    let challenge = app.get_challenge_for_user(&user_address).await?;
+    let signature = app.sign(&challenge.message).await?;
 
     let auth_json = app
-        .web3_auth(signature.to_string().as_str(), &user_address)
+        .web3_auth(signature.to_string().as_str(), &challenge.message)
         .await?;
 
     let jwt = auth_json.get("jwt").unwrap().as_str().unwrap();
@@ -24,12 +31,330 @@ async fn web3_auth_works_correctly() -> Result<()> {
         .decode(public_key_base64)
         .unwrap();
     let decoding_key = DecodingKey::from_ed_der(&public_key_bytes);
-    let _ = jsonwebtoken::decode::<Claims>(
+    let claims = jsonwebtoken::decode::<Claims>(
         jwt,
         &decoding_key,
         &jsonwebtoken::Validation::new(Algorithm::EdDSA),
     )
-    .unwrap();
+    .unwrap()
+    .claims;
+
+    assert_eq!(user_address, claims.wallet);
+    assert_eq!(1, claims.chain_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn web3_auth_records_chain_of_the_nonce() -> Result<()> {
+    let app = spawn_app().await;
+    let user_address = app.user_address();
+    let challenge = app
+        .get_challenge_for_user_on_chain(&user_address, 137)
+        .await?;
+    let message: Message = challenge.message.parse()?;
+    assert_eq!(137, message.chain_id);
+    let signature = app.sign(&challenge.message).await?;
+
+    let auth_json = app
+        .web3_auth(signature.to_string().as_str(), &challenge.message)
+        .await?;
+
+    let jwt = auth_json.get("jwt").unwrap().as_str().unwrap();
+    let claims = decode_claims(jwt)?;
+    assert_eq!(137, claims.chain_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn nonce_for_disallowed_chain_is_not_issued() -> Result<()> {
+    let app = spawn_app().await;
+    let user_address = app.user_address();
+
+    let response = reqwest::Client::new()
+        .get(format!(
+            "http://{}/users/{user_address}/nonce?chain_id=56",
+            app.address
+        ))
+        .send()
+        .await?;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn web3_auth_rejects_signature_of_bare_nonce() -> Result<()> {
+    let app = spawn_app().await;
+    let user_address = app.user_address();
+    let challenge = app.get_challenge_for_user(&user_address).await?;
+    let signature = app.sign(challenge.nonce.to_string().as_str()).await?;
+
+    let response = app
+        .web3_auth_response(signature.to_string().as_str(), &challenge.message)
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    Ok(())
+}
+
+fn foreign_domain(message: &mut Message) {
+    message.domain = "evil.com".parse().unwrap();
+}
+
+fn foreign_uri(message: &mut Message) {
+    message.uri = "https://evil.com".parse().unwrap();
+}
+
+fn foreign_chain(message: &mut Message) {
+    message.chain_id = 137;
+}
+
+fn disallowed_chain(message: &mut Message) {
+    message.chain_id = 56;
+}
+
+fn foreign_nonce(message: &mut Message) {
+    message.nonce = uuid::Uuid::new_v4().simple().to_string();
+}
+
+fn expired(message: &mut Message) {
+    message.expiration_time = Some("2000-01-01T00:00:00.000Z".parse().unwrap());
+}
+
+fn premature(message: &mut Message) {
+    message.not_before = Some("2100-01-01T00:00:00.000Z".parse().unwrap());
+}
+
+#[rstest]
+#[case::foreign_domain(foreign_domain, "Message domain doesn't match")]
+#[case::foreign_uri(foreign_uri, "Message uri doesn't match")]
+#[case::foreign_chain(foreign_chain, "Message chain id doesn't match")]
+#[case::disallowed_chain(disallowed_chain, "Chain is not allowed")]
+#[case::foreign_nonce(foreign_nonce, "Message nonce doesn't match")]
+#[case::expired(expired, "Message is expired")]
+#[case::premature(premature, "Message is not valid yet")]
+#[tokio::test]
+async fn web3_auth_rejects_tampered_message(
+    #[case] tamper: fn(&mut Message),
+    #[case] expected_error: &str,
+) -> Result<()> {
+    let app = spawn_app().await;
+    let user_address = app.user_address();
+    let challenge = app.get_challenge_for_user(&user_address).await?;
+    let mut message: Message = challenge.message.parse()?;
+    tamper(&mut message);
+    let message = message.to_string();
+    let signature = app.sign(&message).await?;
+
+    let response = app
+        .web3_auth_response(signature.to_string().as_str(), &message)
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let JsonResponse::Error(error) = response.json::<JsonResponse<String>>().await? else {
+        panic!("Expected error response");
+    };
+    assert_eq!(expected_error, error);
+
+    Ok(())
+}
+
+const CONTRACT_CODE: &str = "0x6080";
+const MAGIC_VALUE: &str = "0x1626ba7e00000000000000000000000000000000000000000000000000000000";
+const NON_MAGIC_VALUE: &str = "0xffffffff00000000000000000000000000000000000000000000000000000000";
+
+async fn spawn_app_with_rpc(results: HashMap<&'static str, Value>, delay: Duration) -> TestApp {
+    let rpc_url = spawn_mock_rpc(results, delay);
+    spawn_app_with_config(|config| {
+        config.ethereum = Some(EthereumConfig {
+            rpc_url,
+            request_timeout_ms: 500,
+        })
+    })
+    .await
+}
+
+/// Sign in on behalf of a contract wallet with the signature of its owner.
+async fn contract_wallet_auth(app: &TestApp) -> Result<reqwest::Response> {
+    let contract_address = LocalWallet::new(&mut rand::thread_rng()).address().to_hex();
+    let challenge = app.get_challenge_for_user(&contract_address).await?;
+    let signature = app.sign(&challenge.message).await?;
+
+    app.web3_auth_response(signature.to_string().as_str(), &challenge.message)
+        .await
+}
+
+#[tokio::test]
+async fn contract_wallet_signature_is_accepted() -> Result<()> {
+    let results = HashMap::from([
+        ("eth_getCode", json!(CONTRACT_CODE)),
+        ("eth_call", json!(MAGIC_VALUE)),
+    ]);
+    let app = spawn_app_with_rpc(results, Duration::ZERO).await;
+
+    let response = contract_wallet_auth(&app).await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn contract_wallet_rejection_is_reported() -> Result<()> {
+    let results = HashMap::from([
+        ("eth_getCode", json!(CONTRACT_CODE)),
+        ("eth_call", json!(NON_MAGIC_VALUE)),
+    ]);
+    let app = spawn_app_with_rpc(results, Duration::ZERO).await;
+
+    let response = contract_wallet_auth(&app).await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response
+        .text()
+        .await?
+        .contains("Contract wallet rejected the signature"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_signature_of_account_without_code_is_rejected() -> Result<()> {
+    let results = HashMap::from([("eth_getCode", json!("0x"))]);
+    let app = spawn_app_with_rpc(results, Duration::ZERO).await;
+
+    let response = contract_wallet_auth(&app).await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response
+        .text()
+        .await?
+        .contains("Signature verification error"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn slow_ethereum_node_causes_timeout() -> Result<()> {
+    let results = HashMap::from([
+        ("eth_getCode", json!(CONTRACT_CODE)),
+        ("eth_call", json!(MAGIC_VALUE)),
+    ]);
+    let app = spawn_app_with_rpc(results, Duration::from_secs(2)).await;
+
+    let response = contract_wallet_auth(&app).await?;
+
+    assert_eq!(StatusCode::GATEWAY_TIMEOUT, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failure_of_ethereum_node_is_not_exposed() -> Result<()> {
+    let results = HashMap::from([("eth_getCode", json!(CONTRACT_CODE))]);
+    let app = spawn_app_with_rpc(results, Duration::ZERO).await;
+
+    let response = contract_wallet_auth(&app).await?;
+
+    assert_eq!(StatusCode::BAD_GATEWAY, response.status());
+    let JsonResponse::Error(error) = response.json::<JsonResponse<String>>().await? else {
+        panic!("Expected error response");
+    };
+    assert_eq!("Bad Gateway", error);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn eoa_signature_does_not_require_ethereum_node() -> Result<()> {
+    let app = spawn_app_with_rpc(HashMap::new(), Duration::ZERO).await;
+
+    app.login().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn web3_auth_with_typed_data_works_correctly() -> Result<()> {
+    let app = spawn_app().await;
+    let user_address = app.user_address();
+    let (nonce, typed_data) = app.get_typed_data_challenge_for_user(&user_address).await?;
+    assert_eq!("Battlemon", typed_data.domain.name.as_deref().unwrap());
+    assert_eq!(
+        user_address,
+        typed_data.message["wallet"]
+            .as_str()
+            .unwrap()
+            .to_lowercase()
+    );
+
+    let signature = app.sign_typed_data(&typed_data).await?;
+    let response = app
+        .web3_auth_typed_data_response(&signature.to_string(), &user_address, &nonce)
+        .await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let JsonResponse::Success(auth_json) = response.json::<JsonResponse<Value>>().await? else {
+        panic!("Expected success response");
+    };
+    assert!(auth_json.get("jwt").unwrap().is_string());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn typed_data_signed_for_another_domain_is_rejected() -> Result<()> {
+    let app = spawn_app().await;
+    let user_address = app.user_address();
+    let (nonce, mut typed_data) = app.get_typed_data_challenge_for_user(&user_address).await?;
+    typed_data.domain.name = Some("Evil".to_owned());
+
+    let signature = app.sign_typed_data(&typed_data).await?;
+    let response = app
+        .web3_auth_typed_data_response(&signature.to_string(), &user_address, &nonce)
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn typed_data_signature_is_not_accepted_as_personal_sign() -> Result<()> {
+    let app = spawn_app().await;
+    let user_address = app.user_address();
+    let (nonce, typed_data) = app.get_typed_data_challenge_for_user(&user_address).await?;
+    let signature = app.sign(&serde_json::to_string(&typed_data)?).await?;
+
+    let response = app
+        .web3_auth_typed_data_response(&signature.to_string(), &user_address, &nonce)
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn typed_data_mode_requires_nonce() -> Result<()> {
+    let app = spawn_app().await;
+    let json = json!({
+        "mode": "typed_data",
+        "signature": "0x00",
+        "user_id": app.user_address(),
+    });
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/web3_auth", app.address))
+        .json(&json)
+        .send()
+        .await?;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
 
     Ok(())
 }
@@ -0,0 +1,106 @@
+mod helpers;
+
+use eyre::Result;
+use helpers::{spawn_app, spawn_app_with_config, TestApp};
+use reqwest::{
+    header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+        ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE,
+        ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+    },
+    Client, Method, Response, StatusCode,
+};
+
+async fn preflight(app: &TestApp, path: &str, origin: &str) -> Result<Response> {
+    let response = Client::new()
+        .request(Method::OPTIONS, format!("http://{}/{path}", app.address))
+        .header(ORIGIN, origin)
+        .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .header(ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+        .send()
+        .await?;
+
+    Ok(response)
+}
+
+fn header(response: &Response, name: impl reqwest::header::AsHeaderName) -> Option<&str> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+#[tokio::test]
+async fn preflight_of_allowed_origin_succeeds() -> Result<()> {
+    let app = spawn_app().await;
+
+    let response = preflight(&app, "web3_auth", "http://localhost:3000").await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        Some("http://localhost:3000"),
+        header(&response, ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+    assert_eq!(
+        Some("true"),
+        header(&response, ACCESS_CONTROL_ALLOW_CREDENTIALS)
+    );
+    assert!(header(&response, ACCESS_CONTROL_ALLOW_METHODS)
+        .unwrap_or_default()
+        .contains("POST"));
+    assert!(header(&response, ACCESS_CONTROL_ALLOW_HEADERS)
+        .unwrap_or_default()
+        .contains("content-type"));
+    assert_eq!(Some("600"), header(&response, ACCESS_CONTROL_MAX_AGE));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn preflight_of_unknown_origin_is_not_allowed() -> Result<()> {
+    let app = spawn_app().await;
+
+    let response = preflight(&app, "web3_auth", "https://evil.com").await?;
+
+    assert_eq!(None, header(&response, ACCESS_CONTROL_ALLOW_ORIGIN));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn subdomains_of_wildcard_origin_are_allowed() -> Result<()> {
+    let app = spawn_app_with_config(|config| {
+        config.cors.allowed_origins = vec!["https://*.battlemon.com".to_owned()];
+    })
+    .await;
+
+    let subdomain = preflight(&app, "web3_auth", "https://game.battlemon.com").await?;
+    let other_domain = preflight(&app, "web3_auth", "https://game.evilbattlemon.com").await?;
+
+    assert_eq!(
+        Some("https://game.battlemon.com"),
+        header(&subdomain, ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+    assert_eq!(None, header(&other_domain, ACCESS_CONTROL_ALLOW_ORIGIN));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn response_to_allowed_origin_contains_cors_headers() -> Result<()> {
+    let app = spawn_app().await;
+
+    let response = Client::new()
+        .get(format!("http://{}/health/live", app.address))
+        .header(ORIGIN, "http://127.0.0.1:3000")
+        .send()
+        .await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        Some("http://127.0.0.1:3000"),
+        header(&response, ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+mod helpers;
+
+use eyre::{bail, Result};
+use helpers::{
+    decode_claims, jwt_of, profile_of, spawn_app, spawn_app_with_config, spawn_mock_rpc, TestApp,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::{collections::HashMap, time::Duration};
+
+use battlemon_ethereum::{config::EthereumConfig, routes::JsonResponse};
+
+const ENS_NAME: &str = "player.eth";
+
+/// Put the name of the app wallet into cache as if it was resolved `age` ago.
+async fn cache_ens_name(app: &TestApp, name: Option<&str>, age: chrono::Duration) -> Result<()> {
+    let user_id = app.user_address();
+    app.get_nonce_for_user(&user_id).await?;
+    sqlx::query!(
+        r#"
+        insert into ens_names(user_id, name, resolved_at)
+        values ($1, $2, $3)
+        "#,
+        user_id,
+        name,
+        chrono::Utc::now() - age
+    )
+    .execute(&app.db_pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn spawn_app_with_rpc(results: HashMap<&'static str, Value>) -> TestApp {
+    let rpc_url = spawn_mock_rpc(results, Duration::ZERO);
+    spawn_app_with_config(|config| {
+        config.ethereum = Some(EthereumConfig {
+            rpc_url,
+            request_timeout_ms: 500,
+        })
+    })
+    .await
+}
+
+#[tokio::test]
+async fn cached_ens_name_is_included_in_claims_and_profile() -> Result<()> {
+    let app = spawn_app().await;
+    cache_ens_name(&app, Some(ENS_NAME), chrono::Duration::zero()).await?;
+
+    let tokens = app.login().await?;
+
+    assert_eq!(
+        Some(ENS_NAME),
+        decode_claims(jwt_of(&tokens))?.ens.as_deref()
+    );
+    let response = app.get_with_bearer("me", jwt_of(&tokens)).await?;
+    let profile = profile_of(response).await?;
+    assert_eq!(Some(ENS_NAME), profile.wallets[0].ens_name.as_deref());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn outdated_ens_name_is_resolved_again() -> Result<()> {
+    // Absent resolver means the address doesn't have a primary name anymore.
+    let results = HashMap::from([("eth_call", json!("0x"))]);
+    let app = spawn_app_with_rpc(results).await;
+    cache_ens_name(&app, Some(ENS_NAME), chrono::Duration::days(30)).await?;
+
+    let tokens = app.login().await?;
+
+    assert_eq!(None, decode_claims(jwt_of(&tokens))?.ens);
+    let cached_name = sqlx::query_scalar!(
+        r#"
+        select name from ens_names
+        where user_id = $1
+        "#,
+        app.user_address()
+    )
+    .fetch_one(&app.db_pool)
+    .await?;
+    assert_eq!(None, cached_name);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unavailable_ethereum_node_does_not_break_login() -> Result<()> {
+    let app = spawn_app_with_rpc(HashMap::new()).await;
+    cache_ens_name(&app, Some(ENS_NAME), chrono::Duration::days(30)).await?;
+
+    let tokens = app.login().await?;
+
+    assert_eq!(
+        Some(ENS_NAME),
+        decode_claims(jwt_of(&tokens))?.ens.as_deref()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn profile_is_found_by_ens_name() -> Result<()> {
+    let app = spawn_app().await;
+    cache_ens_name(&app, Some(ENS_NAME), chrono::Duration::zero()).await?;
+    app.login().await?;
+
+    let response = app.get(&format!("users/{ENS_NAME}"), None).await?;
+
+    let profile = profile_of(response).await?;
+    assert_eq!(app.user_address(), profile.wallets[0].address);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_ens_name_is_not_found() -> Result<()> {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("http://{}/users/unknown.eth", app.address)).await?;
+
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failure_of_ethereum_node_is_not_exposed_by_profile() -> Result<()> {
+    let app = spawn_app_with_rpc(HashMap::new()).await;
+
+    let response = reqwest::get(format!("http://{}/users/unknown.eth", app.address)).await?;
+
+    assert_eq!(StatusCode::BAD_GATEWAY, response.status());
+    let JsonResponse::Error(error) = response.json::<JsonResponse<String>>().await? else {
+        bail!("Expected error response");
+    };
+    assert_eq!("Bad Gateway", error);
+
+    Ok(())
+}
@@ -1,7 +1,17 @@
 mod helpers;
+use std::{net::TcpListener, time::Duration};
+
+use axum::{extract::State, response::IntoResponse};
 use eyre::Result;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 
-use helpers::spawn_app;
+use battlemon_ethereum::{
+    config::EthereumConfig,
+    routes::{DependencyStatus, JsonResponse, Readiness},
+    startup::MIGRATOR,
+};
+use helpers::{spawn_app, spawn_app_with_config, TestApp};
+use reqwest::StatusCode;
 
 #[tokio::test]
 async fn healthcheck_success() -> Result<()> {
@@ -14,3 +24,105 @@ async fn healthcheck_success() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn liveness_success() -> Result<()> {
+    let app = spawn_app().await;
+
+    let response = app.get("health/live", None).await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+
+    Ok(())
+}
+
+async fn readiness(app: &TestApp) -> Result<(StatusCode, Readiness)> {
+    let response = reqwest::get(format!("http://{}/health/ready", app.address)).await?;
+    let status = response.status();
+    let readiness = match response.json::<JsonResponse<Readiness>>().await? {
+        JsonResponse::Success(readiness) | JsonResponse::Error(readiness) => readiness,
+    };
+
+    Ok((status, readiness))
+}
+
+#[tokio::test]
+async fn ready_when_database_is_migrated() -> Result<()> {
+    let app = spawn_app().await;
+
+    let (status, readiness) = readiness(&app).await?;
+
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!(DependencyStatus::Up, readiness.checks["database"].status);
+    assert_eq!(DependencyStatus::Up, readiness.checks["migrations"].status);
+    assert!(!readiness.checks.contains_key("ethereum"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn not_ready_with_pending_migrations() -> Result<()> {
+    let app = spawn_app().await;
+    let version = sqlx::query_scalar!("select max(version) from _sqlx_migrations")
+        .fetch_one(&app.db_pool)
+        .await?;
+    sqlx::query!("delete from _sqlx_migrations where version = $1", version)
+        .execute(&app.db_pool)
+        .await?;
+
+    let (status, readiness) = readiness(&app).await?;
+
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, status);
+    let migrations = &readiness.checks["migrations"];
+    assert_eq!(DependencyStatus::Down, migrations.status);
+    assert_eq!(Some("Check failed"), migrations.error.as_deref());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn not_ready_when_ethereum_node_is_unreachable() -> Result<()> {
+    let app = spawn_app_with_config(|config| {
+        config.ethereum = Some(EthereumConfig {
+            rpc_url: "http://127.0.0.1:1".to_owned(),
+            request_timeout_ms: 500,
+        })
+    })
+    .await;
+
+    let (status, readiness) = readiness(&app).await?;
+
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, status);
+    assert_eq!(DependencyStatus::Up, readiness.checks["database"].status);
+    assert_eq!(DependencyStatus::Down, readiness.checks["ethereum"].status);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn not_ready_when_database_is_unreachable() -> Result<()> {
+    // Nothing listens on the port once the listener is dropped.
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let db_pool = PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(500))
+        .connect_lazy_with(PgConnectOptions::new().host("127.0.0.1").port(port));
+
+    let response =
+        battlemon_ethereum::routes::readiness(State(None), State(&MIGRATOR), State(db_pool))
+            .await
+            .into_response();
+
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, response.status());
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    let JsonResponse::Error(readiness) = serde_json::from_slice::<JsonResponse<Readiness>>(&body)?
+    else {
+        eyre::bail!("Expected error response");
+    };
+    assert_eq!(DependencyStatus::Down, readiness.checks["database"].status);
+    assert_eq!(
+        DependencyStatus::Down,
+        readiness.checks["migrations"].status
+    );
+
+    Ok(())
+}
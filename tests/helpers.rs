@@ -1,16 +1,29 @@
-use ethers::prelude::{rand, LocalWallet, Signature, Signer};
+#![allow(dead_code)]
+
+use axum::{routing::post, Json, Router};
+use base64::Engine;
+use ethers::{
+    prelude::{rand, LocalWallet, Signature, Signer},
+    types::transaction::eip712::TypedData,
+};
 use eyre::{bail, ensure, Result, WrapErr};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use once_cell::sync::Lazy;
 use reqwest::{Client, Method, RequestBuilder, Response};
+use secrecy::ExposeSecret;
 use serde::Serialize;
 use serde_json::{json, Value};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
+use std::{collections::HashMap, net::TcpListener, sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 use battlemon_ethereum::{
     address::ToHex,
-    config::{load_config, DatabaseConfig},
-    routes::JsonResponse,
+    config::{load_config, DatabaseConfig, MainConfig},
+    jwt::Claims,
+    routes::{Challenge, JsonResponse, Profile},
     startup::App,
     telemetry::{build_subscriber, init_subscriber},
 };
@@ -19,15 +32,26 @@ static TRACING: Lazy<()> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
     let subscriber_name = "test".to_string();
     let subscriber = if std::env::var("TEST_LOG").is_ok() {
-        build_subscriber(subscriber_name, default_filter_level, std::io::stdout)
+        build_subscriber(subscriber_name, default_filter_level, std::io::stdout, None)
     } else {
-        build_subscriber(subscriber_name, default_filter_level, std::io::sink)
+        build_subscriber(subscriber_name, default_filter_level, std::io::sink, None)
     };
     init_subscriber(subscriber).expect("Failed to init subscriber");
 });
 
+pub struct PersonalSignChallenge {
+    pub nonce: Uuid,
+    pub message: String,
+}
+
 pub struct TestApp {
     pub address: String,
+    /// Address of the admin port, if it's configured.
+    pub admin_address: Option<String>,
+    /// Stops the app as a shutdown signal does.
+    pub shutdown: CancellationToken,
+    /// Task running the app, which completes once the app has stopped.
+    pub server: JoinHandle<Result<()>>,
     // pub db_name: String,
     pub db_pool: PgPool,
     pub wallet: LocalWallet,
@@ -47,8 +71,11 @@ impl TestApp {
         query: Option<&str>,
         json: Option<T>,
     ) -> RequestBuilder {
-        let url = format!("http://{}/{path}", self.address);
-        let ret = Client::new().request(method, url).query(&query);
+        let url = match query {
+            None => format!("http://{}/{path}", self.address),
+            Some(query) => format!("http://{}/{path}?{query}", self.address),
+        };
+        let ret = Client::new().request(method, url);
 
         match json {
             None => ret,
@@ -84,27 +111,82 @@ impl TestApp {
         assert_success_status(response).await
     }
 
-    pub async fn get_nonce_for_user(&self, user_id: &str) -> Result<Uuid> {
+    async fn request_challenge(&self, user_id: &str, query: &str) -> Result<Challenge> {
         let response = self
-            .get(&format!("users/{user_id}/nonce"), None)
+            .get(&format!("users/{user_id}/nonce"), Some(query))
             .await
             .wrap_err("Failed to get nonce for user")?;
 
-        let Ok(JsonResponse::Success(uuid)) = response.json().await else {
-               bail!("Failed to deserialize `Uuid` from `Value`");
+        let Ok(JsonResponse::Success(challenge)) = response.json().await else {
+            bail!("Failed to deserialize `Challenge` from `Value`");
         };
 
-        Ok(uuid)
+        Ok(challenge)
+    }
+
+    pub async fn get_challenge_for_user(&self, user_id: &str) -> Result<PersonalSignChallenge> {
+        self.get_challenge_for_user_with_query(user_id, "mode=personal_sign")
+            .await
+    }
+
+    pub async fn get_challenge_for_user_on_chain(
+        &self,
+        user_id: &str,
+        chain_id: u64,
+    ) -> Result<PersonalSignChallenge> {
+        self.get_challenge_for_user_with_query(user_id, &format!("chain_id={chain_id}"))
+            .await
+    }
+
+    async fn get_challenge_for_user_with_query(
+        &self,
+        user_id: &str,
+        query: &str,
+    ) -> Result<PersonalSignChallenge> {
+        let challenge = self.request_challenge(user_id, query).await?;
+        let Some(message) = challenge.message else {
+            bail!("Challenge doesn't contain message");
+        };
+
+        Ok(PersonalSignChallenge {
+            nonce: challenge.nonce,
+            message,
+        })
+    }
+
+    pub async fn get_typed_data_challenge_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<(Uuid, TypedData)> {
+        let challenge = self.request_challenge(user_id, "mode=typed_data").await?;
+        let Some(typed_data) = challenge.typed_data else {
+            bail!("Challenge doesn't contain typed data");
+        };
+
+        Ok((challenge.nonce, typed_data))
+    }
+
+    pub async fn get_nonce_for_user(&self, user_id: &str) -> Result<Uuid> {
+        let challenge = self.get_challenge_for_user(user_id).await?;
+
+        Ok(challenge.nonce)
     }
 
-    pub async fn web3_auth(&self, signature: &str, user_id: &str) -> Result<Value> {
+    pub async fn web3_auth_response(&self, signature: &str, message: &str) -> Result<Response> {
         let json = json!({
             "signature": signature,
-            "user_id": user_id,
+            "message": message,
         });
 
-        let response = self
-            .post("web3_auth", Some(json))
+        self.http_post_builder("web3_auth", Some(json))
+            .send()
+            .await
+            .wrap_err("Failed to make request")
+    }
+
+    pub async fn web3_auth(&self, signature: &str, message: &str) -> Result<Value> {
+        let response = self.web3_auth_response(signature, message).await?;
+        let response = assert_success_status(response)
             .await
             .wrap_err("Failed to authenticate user")?;
 
@@ -115,6 +197,107 @@ impl TestApp {
         Ok(value)
     }
 
+    /// Pass the whole sign-in flow and return the issued tokens.
+    pub async fn login(&self) -> Result<Value> {
+        self.login_with(&self.wallet).await
+    }
+
+    /// Sign in with a wallet other than the default one of the app.
+    pub async fn login_with(&self, wallet: &LocalWallet) -> Result<Value> {
+        let challenge = self
+            .get_challenge_for_user(&wallet.address().to_hex())
+            .await?;
+        let signature = wallet
+            .sign_message(&challenge.message)
+            .await
+            .wrap_err("Failed to sign message")?;
+
+        self.web3_auth(signature.to_string().as_str(), &challenge.message)
+            .await
+    }
+
+    pub async fn refresh_token_response(&self, refresh_token: &str) -> Result<Response> {
+        let json = json!({ "refresh_token": refresh_token });
+
+        self.http_post_builder("token/refresh", Some(json))
+            .send()
+            .await
+            .wrap_err("Failed to make request")
+    }
+
+    pub async fn post_with_bearer<T: Serialize>(
+        &self,
+        path: &str,
+        token: &str,
+        json: Option<T>,
+    ) -> Result<Response> {
+        self.http_post_builder(path, json)
+            .bearer_auth(token)
+            .send()
+            .await
+            .wrap_err("Failed to make request")
+    }
+
+    pub async fn get_with_bearer(&self, path: &str, token: &str) -> Result<Response> {
+        self.http_get_builder(path, None)
+            .bearer_auth(token)
+            .send()
+            .await
+            .wrap_err("Failed to make request")
+    }
+
+    pub async fn patch_with_bearer<T: Serialize>(
+        &self,
+        path: &str,
+        token: &str,
+        json: T,
+    ) -> Result<Response> {
+        self.http_request_builder(Method::PATCH, path, None, Some(json))
+            .bearer_auth(token)
+            .send()
+            .await
+            .wrap_err("Failed to make request")
+    }
+
+    pub async fn put_with_bearer<T: Serialize>(
+        &self,
+        path: &str,
+        token: &str,
+        json: T,
+    ) -> Result<Response> {
+        self.http_request_builder(Method::PUT, path, None, Some(json))
+            .bearer_auth(token)
+            .send()
+            .await
+            .wrap_err("Failed to make request")
+    }
+
+    pub async fn web3_auth_typed_data_response(
+        &self,
+        signature: &str,
+        user_id: &str,
+        nonce: &Uuid,
+    ) -> Result<Response> {
+        let json = json!({
+            "mode": "typed_data",
+            "signature": signature,
+            "user_id": user_id,
+            "nonce": nonce,
+        });
+
+        self.http_post_builder("web3_auth", Some(json))
+            .send()
+            .await
+            .wrap_err("Failed to make request")
+    }
+
+    pub async fn sign_typed_data(&self, typed_data: &TypedData) -> Result<Signature> {
+        self.wallet
+            .sign_typed_data(typed_data)
+            .await
+            .wrap_err("Failed to sign typed data")
+    }
+
     pub async fn sign(&self, message: &str) -> Result<Signature> {
         self.wallet
             .sign_message(message)
@@ -137,21 +320,72 @@ pub async fn assert_success_status(response: Response) -> Result<Response> {
     Ok(response)
 }
 
+/// Read claims of the token without verifying its signature.
+pub fn decode_claims(jwt: &str) -> Result<Claims> {
+    let mut validation = Validation::new(Algorithm::EdDSA);
+    validation.insecure_disable_signature_validation();
+    let claims = jsonwebtoken::decode(jwt, &DecodingKey::from_secret(&[]), &validation)?.claims;
+
+    Ok(claims)
+}
+
+/// Sign the claims with the configured signing key, e.g. to get a token close to expiry.
+pub fn sign_claims(claims: &Claims) -> Result<String> {
+    let secrets = load_config()?.secrets;
+    let key_pair = base64::engine::general_purpose::STANDARD
+        .decode(secrets.key_pairs[&secrets.signing_key_id].expose_secret())?;
+    let mut header = Header::new(Algorithm::EdDSA);
+    header.kid = Some(secrets.signing_key_id);
+
+    jsonwebtoken::encode(&header, claims, &EncodingKey::from_ed_der(&key_pair))
+        .wrap_err("Failed to sign claims")
+}
+
+/// Access token of the tokens issued by sign-in or refresh.
+pub fn jwt_of(json: &Value) -> &str {
+    json.get("jwt").unwrap().as_str().unwrap()
+}
+
+/// Refresh token of the tokens issued by sign-in or refresh.
+pub fn refresh_token_of(json: &Value) -> &str {
+    json.get("refresh_token").unwrap().as_str().unwrap()
+}
+
+pub async fn profile_of(response: Response) -> Result<Profile> {
+    let JsonResponse::Success(profile) = response.json().await? else {
+        bail!("Expected success response");
+    };
+
+    Ok(profile)
+}
+
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with_config(|_| {}).await
+}
+
+pub async fn spawn_app_with_config(customize: impl FnOnce(&mut MainConfig)) -> TestApp {
     Lazy::force(&TRACING);
     let mut config = load_config().expect("Failed to read configuration");
     config.db.db_name = Uuid::new_v4().to_string();
-    let db_pool = configure_database(&config.db).await;
     config.app.port = 0;
+    customize(&mut config);
+    let db_pool = configure_database(&config.db).await;
     let app = App::build(config)
         .await
         .expect("Failed to build app for testing");
     let address = format!("127.0.0.1:{}", app.port());
-    tokio::spawn(app.run_until_stopped());
+    let admin_address = app
+        .admin_port()
+        .map(|admin_port| format!("127.0.0.1:{admin_port}"));
+    let shutdown = app.shutdown_token();
+    let server = tokio::spawn(app.run_until_stopped());
 
     TestApp {
         db_pool,
         address,
+        admin_address,
+        shutdown,
+        server,
         wallet: LocalWallet::new(&mut rand::thread_rng()),
     }
 }
@@ -177,3 +411,38 @@ pub async fn configure_database(config: &DatabaseConfig) -> PgPool {
 
     db_pool
 }
+
+/// Stand-in for Ethereum node, which answers JSON-RPC methods with the given results
+/// after the `delay`. Returns url of the node.
+///
+/// Used instead of a local Anvil node, since verification of contract wallets only needs
+/// `eth_getCode` and `eth_call` results, while timeouts and failures of the node can't be
+/// staged with a real one. Tests then don't depend on a node running next to database.
+pub fn spawn_mock_rpc(results: HashMap<&'static str, Value>, delay: Duration) -> String {
+    let results = Arc::new(results);
+    let router = Router::new().route(
+        "/",
+        post(move |Json(request): Json<Value>| async move {
+            tokio::time::sleep(delay).await;
+            let method = request["method"].as_str().unwrap_or_default();
+            let body = match results.get(method) {
+                Some(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+                None => json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": { "code": -32601, "message": "Method not found" }
+                }),
+            };
+
+            Json(body)
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind address for mock rpc");
+    let address = listener.local_addr().unwrap();
+    let server = axum::Server::from_tcp(listener)
+        .expect("Failed to create mock rpc server")
+        .serve(router.into_make_service());
+    tokio::spawn(server);
+
+    format!("http://{address}")
+}
@@ -0,0 +1,279 @@
+mod helpers;
+
+use ethers::{
+    prelude::{
+        Address, Http, LocalWallet, Middleware, Provider, Signer, SignerMiddleware,
+        TransactionRequest, H256,
+    },
+    utils::{keccak256, Anvil},
+};
+use eyre::{bail, eyre, Result};
+use helpers::{spawn_app_with_config, spawn_mock_rpc, TestApp};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::{collections::HashMap, time::Duration};
+
+use battlemon_ethereum::{
+    address::ToHex,
+    config::{EthereumConfig, IndexedContractConfig, IndexerConfig},
+    routes::{JsonResponse, TokenOwner},
+};
+
+const CONTRACT: &str = "0x00000000000000000000000000000000000000aa";
+const OWNER: &str = "0x00000000000000000000000000000000000000bb";
+const STALE_OWNER: &str = "0x00000000000000000000000000000000000000cc";
+const TOKEN_ID: u64 = 7;
+const BLOCK_HASH: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+
+fn transfer_topic() -> H256 {
+    H256::from(keccak256("Transfer(address,address,uint256)"))
+}
+
+fn address_topic(address: &str) -> String {
+    format!("{:#x}", H256::from(address.parse::<Address>().unwrap()))
+}
+
+/// Node at block 20, whose only transfer of the contract mints the token to `OWNER` at block 12.
+fn mock_rpc() -> String {
+    let log = json!({
+        "address": CONTRACT,
+        "topics": [
+            format!("{:#x}", transfer_topic()),
+            address_topic(&Address::zero().to_hex()),
+            address_topic(OWNER),
+            format!("{:#x}", H256::from_low_u64_be(TOKEN_ID)),
+        ],
+        "data": "0x",
+        "blockHash": BLOCK_HASH,
+        "blockNumber": "0xc",
+        "transactionHash": BLOCK_HASH,
+        "transactionIndex": "0x0",
+        "logIndex": "0x0",
+        "removed": false,
+    });
+    let results = HashMap::from([
+        ("eth_blockNumber", json!("0x14")),
+        (
+            "eth_getBlockByNumber",
+            json!({ "hash": BLOCK_HASH, "number": "0xc" }),
+        ),
+        ("eth_getLogs", json!([log])),
+    ]);
+
+    spawn_mock_rpc(results, Duration::ZERO)
+}
+
+async fn spawn_app_with_indexer(rpc_url: String, contract: Address, confirmations: u64) -> TestApp {
+    spawn_app_with_config(|config| {
+        config.ethereum = Some(EthereumConfig {
+            rpc_url,
+            request_timeout_ms: 500,
+        });
+        config.indexer = Some(IndexerConfig {
+            confirmations,
+            poll_interval_ms: 300,
+            max_block_range: 1000,
+            contracts: vec![IndexedContractConfig {
+                address: contract,
+                start_block: 0,
+            }],
+        });
+    })
+    .await
+}
+
+async fn token_owner_response(app: &TestApp, contract: &str, token_id: u64) -> Result<Value> {
+    let response = reqwest::get(format!(
+        "http://{}/contracts/{contract}/tokens/{token_id}",
+        app.address
+    ))
+    .await?;
+
+    Ok(json!({ "status": response.status().as_u16(), "body": response.json::<Value>().await? }))
+}
+
+/// Poll the index until the token gets the expected owner.
+async fn wait_for_owner(
+    app: &TestApp,
+    contract: &str,
+    token_id: u64,
+    expected_owner: &str,
+) -> Result<TokenOwner> {
+    for _ in 0..50 {
+        let response = token_owner_response(app, contract, token_id).await?;
+        if response["status"] == StatusCode::OK.as_u16() {
+            let JsonResponse::Success(owner) =
+                serde_json::from_value::<JsonResponse<TokenOwner>>(response["body"].clone())?
+            else {
+                bail!("Expected success response");
+            };
+            if owner.owner == expected_owner {
+                return Ok(owner);
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    bail!("Token {token_id} isn't owned by {expected_owner} in time")
+}
+
+#[tokio::test]
+async fn transfers_are_indexed() -> Result<()> {
+    let app = spawn_app_with_indexer(mock_rpc(), CONTRACT.parse()?, 2).await;
+
+    let owner = wait_for_owner(&app, CONTRACT, TOKEN_ID, OWNER).await?;
+
+    assert_eq!(12, owner.block_number);
+    let checkpoint = sqlx::query_scalar!(
+        r#"
+        select block_number from indexer_checkpoints
+        where contract = $1
+        "#,
+        CONTRACT
+    )
+    .fetch_one(&app.db_pool)
+    .await?;
+    assert_eq!(18, checkpoint);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn orphaned_transfers_are_dropped_on_reorg() -> Result<()> {
+    let app = spawn_app_with_indexer(mock_rpc(), CONTRACT.parse()?, 2).await;
+    // Block 14 was indexed on a fork, which the node doesn't know anymore.
+    let orphaned_hash = format!("{:#x}", H256::repeat_byte(0xde));
+    let mut tx = app.db_pool.begin().await?;
+    sqlx::query!(
+        r#"
+        insert into indexer_checkpoints(contract, block_number, block_hash)
+        values ($1, 14, $2)
+        on conflict (contract) do update set block_number = 14, block_hash = $2
+        "#,
+        CONTRACT,
+        orphaned_hash
+    )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        insert into token_transfers(contract, block_number, log_index, block_hash, token_id, from_address, to_address)
+        values ($1, 14, 0, $2, $3, $4, $5)
+        "#,
+        CONTRACT,
+        orphaned_hash,
+        TOKEN_ID.to_string(),
+        OWNER,
+        STALE_OWNER
+    )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        insert into token_owners(contract, token_id, owner, block_number, log_index)
+        values ($1, $2, $3, 14, 0)
+        on conflict (contract, token_id) do update set owner = $3, block_number = 14, log_index = 0
+        "#,
+        CONTRACT,
+        TOKEN_ID.to_string(),
+        STALE_OWNER
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    let owner = wait_for_owner(&app, CONTRACT, TOKEN_ID, OWNER).await?;
+
+    assert_eq!(12, owner.block_number);
+    let orphaned_transfers = sqlx::query_scalar!(
+        r#"
+        select count(*) as "count!" from token_transfers
+        where contract = $1 and block_hash = $2
+        "#,
+        CONTRACT,
+        orphaned_hash
+    )
+    .fetch_one(&app.db_pool)
+    .await?;
+    assert_eq!(0, orphaned_transfers);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_token_is_not_found() -> Result<()> {
+    let app = spawn_app_with_indexer(mock_rpc(), CONTRACT.parse()?, 2).await;
+
+    let response = token_owner_response(&app, CONTRACT, TOKEN_ID + 1).await?;
+
+    assert_eq!(StatusCode::NOT_FOUND.as_u16(), response["status"]);
+
+    Ok(())
+}
+
+/// Contract, which emits `Transfer` of the token id from the calldata to the caller on any call.
+fn minting_contract_code() -> Vec<u8> {
+    let mut runtime = vec![
+        0x60, 0x00, // PUSH1 0
+        0x35, // CALLDATALOAD, token id
+        0x33, // CALLER, recipient
+        0x60, 0x00, // PUSH1 0, sender
+        0x7f, // PUSH32 event signature
+    ];
+    runtime.extend_from_slice(transfer_topic().as_bytes());
+    runtime.extend_from_slice(&[
+        0x60, 0x00, // PUSH1 0, data size
+        0x60, 0x00, // PUSH1 0, data offset
+        0xa4, // LOG4
+        0x00, // STOP
+    ]);
+    // Copy the runtime code placed after the 11 bytes of init code into memory and return it.
+    #[rustfmt::skip]
+    let init = [
+        0x60, runtime.len() as u8, 0x80, 0x60, 0x0b, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3,
+    ];
+
+    [init.as_slice(), runtime.as_slice()].concat()
+}
+
+#[tokio::test]
+#[ignore = "requires anvil binary"]
+async fn transfers_are_indexed_from_anvil() -> Result<()> {
+    let anvil = Anvil::new().spawn();
+    let wallet = LocalWallet::from(anvil.keys()[0].clone()).with_chain_id(anvil.chain_id());
+    let provider = Provider::<Http>::try_from(anvil.endpoint())?;
+    let client = SignerMiddleware::new(provider, wallet);
+    let deployment = client
+        .send_transaction(
+            TransactionRequest::new().data(minting_contract_code()),
+            None,
+        )
+        .await?
+        .await?
+        .ok_or_else(|| eyre!("Deployment isn't mined"))?;
+    let contract = deployment
+        .contract_address
+        .ok_or_else(|| eyre!("Contract isn't deployed"))?;
+    client
+        .send_transaction(
+            TransactionRequest::new()
+                .to(contract)
+                .data(H256::from_low_u64_be(TOKEN_ID).as_bytes().to_vec()),
+            None,
+        )
+        .await?
+        .await?;
+    let app = spawn_app_with_indexer(anvil.endpoint(), contract, 0).await;
+
+    let owner = wait_for_owner(
+        &app,
+        &contract.to_hex(),
+        TOKEN_ID,
+        &client.address().to_hex(),
+    )
+    .await?;
+
+    assert_eq!(TOKEN_ID.to_string(), owner.token_id);
+
+    Ok(())
+}
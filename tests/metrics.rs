@@ -0,0 +1,127 @@
+mod helpers;
+
+use eyre::{Result, WrapErr};
+use helpers::{spawn_app, spawn_app_with_config, TestApp};
+use reqwest::{header::CONTENT_TYPE, StatusCode};
+
+/// Value of the sample of `name`, which has all the `labels`.
+fn sample(body: &str, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+    body.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter(|line| {
+            line.strip_prefix(name)
+                .is_some_and(|rest| rest.starts_with(['{', ' ']))
+        })
+        .find(|line| {
+            labels
+                .iter()
+                .all(|(label, value)| line.contains(&format!("{label}=\"{value}\"")))
+        })
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|value| value.parse().ok())
+}
+
+async fn get_metrics(app: &TestApp) -> Result<String> {
+    let response = app.get("metrics", None).await?;
+    let content_type = response.headers().get(CONTENT_TYPE).cloned();
+    assert_eq!(
+        Some("text/plain; version=0.0.4"),
+        content_type.as_ref().and_then(|value| value.to_str().ok())
+    );
+
+    response.text().await.wrap_err("Failed to read metrics")
+}
+
+#[tokio::test]
+async fn requests_and_logins_are_counted() -> Result<()> {
+    let app = spawn_app().await;
+    app.login().await?;
+
+    let body = get_metrics(&app).await?;
+
+    let nonce_requests = [
+        ("method", "GET"),
+        ("route", "/users/:user_id/nonce"),
+        ("status", "200"),
+    ];
+    assert_eq!(
+        Some(1.0),
+        sample(&body, "http_requests_total", &nonce_requests)
+    );
+    assert_eq!(
+        Some(1.0),
+        sample(
+            &body,
+            "http_request_duration_seconds_count",
+            &nonce_requests
+        )
+    );
+    assert_eq!(
+        Some(1.0),
+        sample(&body, "logins_total", &[("outcome", "success")])
+    );
+    assert_eq!(Some(1.0), sample(&body, "nonces_issued_total", &[]));
+    assert_eq!(Some(10.0), sample(&body, "db_pool_max_connections", &[]));
+    assert!(sample(&body, "db_pool_connections", &[("state", "idle")]).is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_logins_are_counted_by_reason() -> Result<()> {
+    let app = spawn_app().await;
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign(&challenge.message).await?.to_string();
+    app.web3_auth(&signature, &challenge.message).await?;
+
+    let response = app
+        .web3_auth_response(&signature, &challenge.message)
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let body = get_metrics(&app).await?;
+    assert_eq!(
+        Some(1.0),
+        sample(
+            &body,
+            "logins_total",
+            &[("outcome", "failure"), ("reason", "consumed_nonce")]
+        )
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn jwt_verification_failures_are_counted() -> Result<()> {
+    let app = spawn_app().await;
+
+    let response = app.get_with_bearer("me", "not.a.token").await?;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let body = get_metrics(&app).await?;
+    assert_eq!(
+        Some(1.0),
+        sample(
+            &body,
+            "jwt_verification_failures_total",
+            &[("reason", "invalid_auth_token")]
+        )
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn metrics_are_served_on_admin_port() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.metrics.admin_port = Some(0)).await;
+    let admin_address = app.admin_address.clone().unwrap();
+
+    let public_response = reqwest::get(format!("http://{}/metrics", app.address)).await?;
+    let admin_response = reqwest::get(format!("http://{admin_address}/metrics")).await?;
+
+    assert_eq!(StatusCode::NOT_FOUND, public_response.status());
+    assert_eq!(StatusCode::OK, admin_response.status());
+
+    Ok(())
+}
@@ -0,0 +1,171 @@
+mod helpers;
+
+use eyre::{bail, Result};
+use helpers::{decode_claims, jwt_of, spawn_app_with_config, spawn_mock_rpc, TestApp};
+use reqwest::StatusCode;
+use serde_json::json;
+use std::{collections::HashMap, time::Duration};
+
+use battlemon_ethereum::{
+    config::{CollectionConfig, EthereumConfig, MainConfig, TokenStandard},
+    nft::CollectionHoldings,
+    routes::JsonResponse,
+};
+
+const COLLECTION: &str = "lemons";
+const BALANCE_OF_ONE: &str = "0x0000000000000000000000000000000000000000000000000000000000000001";
+const BALANCE_OF_ZERO: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Cache absence of ENS name, so the mock node is asked only about the collection.
+async fn skip_ens_lookup(app: &TestApp) -> Result<()> {
+    let user_id = app.user_address();
+    app.get_nonce_for_user(&user_id).await?;
+    sqlx::query!(
+        r#"
+        insert into ens_names(user_id, name, resolved_at)
+        values ($1, null, now())
+        "#,
+        user_id
+    )
+    .execute(&app.db_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Spawn the app with a single ERC-721 collection, whose `balanceOf` returns `balance`.
+async fn spawn_app_with_collection(
+    balance: &'static str,
+    customize: impl FnOnce(&mut MainConfig),
+) -> TestApp {
+    let rpc_url = spawn_mock_rpc(
+        HashMap::from([("eth_call", json!(balance))]),
+        Duration::ZERO,
+    );
+    let app = spawn_app_with_config(|config| {
+        config.ethereum = Some(EthereumConfig {
+            rpc_url,
+            request_timeout_ms: 500,
+        });
+        config.nft.collections = vec![CollectionConfig {
+            name: COLLECTION.to_owned(),
+            address: "0x0000000000000000000000000000000000000001"
+                .parse()
+                .unwrap(),
+            standard: TokenStandard::Erc721,
+            enumerable: false,
+            token_ids: Vec::new(),
+        }];
+        customize(config);
+    })
+    .await;
+    skip_ens_lookup(&app)
+        .await
+        .expect("Failed to cache ENS name");
+
+    app
+}
+
+#[tokio::test]
+async fn held_collections_are_included_in_claims_and_assets() -> Result<()> {
+    let app = spawn_app_with_collection(BALANCE_OF_ONE, |_| {}).await;
+
+    let tokens = app.login().await?;
+
+    assert_eq!(
+        vec![COLLECTION.to_owned()],
+        decode_claims(jwt_of(&tokens))?.collections
+    );
+    let response = app.get_with_bearer("me/assets", jwt_of(&tokens)).await?;
+    let JsonResponse::Success(holdings) = response
+        .json::<JsonResponse<Vec<CollectionHoldings>>>()
+        .await?
+    else {
+        bail!("Expected success response");
+    };
+    assert_eq!(1, holdings.len());
+    assert_eq!(COLLECTION, holdings[0].collection);
+    assert_eq!("1", holdings[0].balance);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_is_refused_without_required_collection() -> Result<()> {
+    let app = spawn_app_with_collection(BALANCE_OF_ZERO, |config| {
+        config.nft.login_requires = vec![COLLECTION.to_owned()];
+    })
+    .await;
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign(&challenge.message).await?;
+
+    let response = app
+        .web3_auth_response(signature.to_string().as_str(), &challenge.message)
+        .await?;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn gated_route_is_refused_without_required_collection() -> Result<()> {
+    let app = spawn_app_with_collection(BALANCE_OF_ZERO, |config| {
+        config.nft.route_requirements =
+            HashMap::from([("/me".to_owned(), vec![COLLECTION.to_owned()])]);
+    })
+    .await;
+    let tokens = app.login().await?;
+
+    let response = app.get_with_bearer("me", jwt_of(&tokens)).await?;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let response = app.get_with_bearer("me/assets", jwt_of(&tokens)).await?;
+    assert_eq!(StatusCode::OK, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failure_of_node_is_not_exposed_by_assets() -> Result<()> {
+    let rpc_url = spawn_mock_rpc(HashMap::new(), Duration::ZERO);
+    let app = spawn_app_with_collection(BALANCE_OF_ONE, |config| {
+        config.ethereum.as_mut().unwrap().rpc_url = rpc_url;
+    })
+    .await;
+    let tokens = app.login().await?;
+
+    let response = app.get_with_bearer("me/assets", jwt_of(&tokens)).await?;
+
+    assert_eq!(StatusCode::BAD_GATEWAY, response.status());
+    let JsonResponse::<String>::Error(error) = response.json().await? else {
+        bail!("Expected error response");
+    };
+    assert_eq!("Bad Gateway", error);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failure_of_node_is_not_exposed_by_gated_login() -> Result<()> {
+    let rpc_url = spawn_mock_rpc(HashMap::new(), Duration::ZERO);
+    let app = spawn_app_with_collection(BALANCE_OF_ONE, |config| {
+        config.ethereum.as_mut().unwrap().rpc_url = rpc_url;
+        config.nft.login_requires = vec![COLLECTION.to_owned()];
+    })
+    .await;
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign(&challenge.message).await?;
+
+    let response = app
+        .web3_auth_response(signature.to_string().as_str(), &challenge.message)
+        .await?;
+
+    assert_eq!(StatusCode::BAD_GATEWAY, response.status());
+    let JsonResponse::<String>::Error(error) = response.json().await? else {
+        bail!("Expected error response");
+    };
+    assert_eq!("Bad Gateway", error);
+
+    Ok(())
+}
@@ -1,8 +1,10 @@
 mod helpers;
 
 use eyre::{Result, WrapErr};
-use helpers::spawn_app;
-use std::collections::HashSet;
+use helpers::{spawn_app, spawn_app_with_config};
+use reqwest::StatusCode;
+use std::{collections::HashSet, time::Duration};
+use uuid::Uuid;
 
 #[tokio::test]
 async fn every_nonce_for_user_is_unique() -> Result<()> {
@@ -32,17 +34,120 @@ async fn nonces_stored_in_database_correctly() -> Result<()> {
         let nonce = app.get_nonce_for_user(&user_id).await?;
         let row = sqlx::query!(
             r#"
-            select nonce from users
-            where user_id = $1 
+            select user_id, expires_at > issued_at as "valid_period!", consumed_at from nonces
+            where nonce = $1
             "#,
-            user_id
+            nonce
         )
         .fetch_one(&app.db_pool)
         .await
         .wrap_err("Failed to fetch stored nonce from database")?;
 
-        assert_eq!(nonce, row.nonce, "Nonces are not equal");
+        assert_eq!(user_id, row.user_id, "Nonce is stored for another user");
+        assert!(row.valid_period, "Nonce expires before it is issued");
+        assert!(row.consumed_at.is_none(), "Fresh nonce is already consumed");
     }
 
     Ok(())
 }
+
+#[tokio::test]
+async fn nonce_is_consumed_after_successful_auth() -> Result<()> {
+    let app = spawn_app().await;
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign(&challenge.message).await?.to_string();
+    app.web3_auth(&signature, &challenge.message).await?;
+
+    let consumed_at = sqlx::query_scalar!(
+        r#"
+        select consumed_at from nonces
+        where nonce = $1
+        "#,
+        challenge.nonce
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .wrap_err("Failed to fetch stored nonce from database")?;
+
+    assert!(consumed_at.is_some(), "Nonce isn't consumed");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn replayed_signature_is_rejected() -> Result<()> {
+    let app = spawn_app().await;
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign(&challenge.message).await?.to_string();
+    app.web3_auth(&signature, &challenge.message).await?;
+
+    let response = app
+        .web3_auth_response(&signature, &challenge.message)
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response
+        .text()
+        .await?
+        .contains("Nonce has been already used"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn expired_nonce_is_rejected() -> Result<()> {
+    let app = spawn_app().await;
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign(&challenge.message).await?.to_string();
+    expire_nonce(&app.db_pool, &challenge.nonce).await?;
+
+    let response = app
+        .web3_auth_response(&signature, &challenge.message)
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response.text().await?.contains("Nonce is expired"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn expired_nonces_are_swept() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.nonce.sweep_interval_secs = 1).await;
+    let user_id = app.user_address();
+    let expired_nonce = app.get_nonce_for_user(&user_id).await?;
+    let fresh_nonce = app.get_nonce_for_user(&user_id).await?;
+    expire_nonce(&app.db_pool, &expired_nonce).await?;
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let stored_nonces = sqlx::query_scalar!(
+        r#"
+        select nonce from nonces
+        where user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .wrap_err("Failed to fetch stored nonces from database")?;
+
+    assert_eq!(vec![fresh_nonce], stored_nonces);
+
+    Ok(())
+}
+
+async fn expire_nonce(db_pool: &sqlx::PgPool, nonce: &Uuid) -> Result<()> {
+    sqlx::query!(
+        r#"
+        update nonces set expires_at = now() - interval '1 second'
+        where nonce = $1
+        "#,
+        nonce
+    )
+    .execute(db_pool)
+    .await
+    .wrap_err("Failed to expire nonce")?;
+
+    Ok(())
+}
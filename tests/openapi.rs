@@ -0,0 +1,125 @@
+mod helpers;
+
+use eyre::Result;
+use helpers::{spawn_app, spawn_app_with_config};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// Documented operations as methods and paths, with parameters replaced by a placeholder.
+fn operations_of_spec(spec: &Value) -> Vec<(String, String)> {
+    spec["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, item)| {
+            let path = path
+                .split('/')
+                .map(|segment| {
+                    if segment.starts_with('{') {
+                        "1"
+                    } else {
+                        segment
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            METHODS
+                .into_iter()
+                .filter(|method| item.get(method).is_some())
+                .map(move |method| (method.to_owned(), path.clone()))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn documented_operations_are_served() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.openapi.docs_enabled = true).await;
+    let spec: Value = app.get("openapi.json", None).await?.json().await?;
+    let client = reqwest::Client::new();
+
+    let operations = operations_of_spec(&spec);
+
+    assert!(operations.contains(&("get".to_owned(), "/docs".to_owned())));
+    for (method, path) in operations {
+        let response = client
+            .request(
+                method.to_uppercase().parse()?,
+                format!("http://{}{path}", app.address),
+            )
+            .send()
+            .await?;
+        let status = response.status();
+        // Unrouted requests are answered by axum with empty bodies, unlike handlers.
+        let body = response.bytes().await?;
+        assert!(
+            status != StatusCode::METHOD_NOT_ALLOWED
+                && !(status == StatusCode::NOT_FOUND && body.is_empty()),
+            "{method} {path} isn't served, status is {status}"
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn specification_describes_payloads_and_errors() -> Result<()> {
+    let app = spawn_app().await;
+
+    let spec: Value = app.get("openapi.json", None).await?.json().await?;
+
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    let schemas = &spec["components"]["schemas"];
+    for schema in ["Payload", "SessionMode", "Profile", "Challenge"] {
+        assert!(schemas.get(schema).is_some(), "Schema {schema} is absent");
+    }
+    let web3_auth = &spec["paths"]["/web3_auth"]["post"];
+    assert_eq!(
+        "#/components/schemas/Payload",
+        web3_auth["requestBody"]["content"]["application/json"]["schema"]["$ref"]
+    );
+    assert_eq!(
+        "#/components/schemas/ErrorResponse",
+        web3_auth["responses"]["401"]["content"]["application/json"]["schema"]["$ref"]
+    );
+    assert!(schemas["ErrorResponse"]["properties"]
+        .get("error")
+        .is_some());
+    let get_me = &spec["paths"]["/me"]["get"];
+    for status in ["400", "401", "403"] {
+        assert!(get_me["responses"].get(status).is_some());
+    }
+    assert!(spec["components"]["securitySchemes"]
+        .get("bearer")
+        .is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn docs_are_served_when_enabled() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.openapi.docs_enabled = true).await;
+
+    let response = app.get("docs", None).await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(response.text().await?.contains("openapi"));
+    let spec: Value = app.get("openapi.json", None).await?.json().await?;
+    assert!(spec["paths"].get("/docs").is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn docs_are_absent_when_disabled() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.openapi.docs_enabled = false).await;
+
+    let response = reqwest::get(format!("http://{}/docs", app.address)).await?;
+
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    let spec: Value = app.get("openapi.json", None).await?.json().await?;
+    assert!(spec["paths"].get("/docs").is_none());
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+mod helpers;
+
+use eyre::Result;
+use helpers::{spawn_app_with_config, TestApp};
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use battlemon_ethereum::config::{RouteRateLimit, TokenBucketConfig};
+
+const NONCE_ROUTE: &str = "/users/:user_id/nonce";
+const OTHER_ADDRESS: &str = "0x4675c7e5baafbffbca748158becba61ef3b0a263";
+
+async fn spawn_app_with_limit(route: &str, limit: RouteRateLimit) -> TestApp {
+    spawn_app_with_config(|config| {
+        config.rate_limit.routes = HashMap::from([(route.to_owned(), limit)]);
+    })
+    .await
+}
+
+async fn nonce_response(app: &TestApp, user_id: &str) -> Result<reqwest::Response> {
+    let response = reqwest::get(format!("http://{}/users/{user_id}/nonce", app.address)).await?;
+
+    Ok(response)
+}
+
+fn single_request_limit() -> Option<TokenBucketConfig> {
+    Some(TokenBucketConfig {
+        burst: 1,
+        per_minute: 1,
+    })
+}
+
+#[tokio::test]
+async fn nonce_requests_for_the_same_address_are_limited() -> Result<()> {
+    let app = spawn_app_with_limit(
+        NONCE_ROUTE,
+        RouteRateLimit {
+            per_ip: None,
+            per_address: single_request_limit(),
+        },
+    )
+    .await;
+
+    assert_eq!(
+        StatusCode::OK,
+        nonce_response(&app, &app.user_address()).await?.status()
+    );
+    let response = nonce_response(&app, &app.user_address()).await?;
+
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, response.status());
+    assert_eq!("60", response.headers()[RETRY_AFTER].to_str()?);
+    let body: Value = response.json().await?;
+    assert_eq!("Too many requests", body["error"]);
+    assert_eq!(
+        StatusCode::OK,
+        nonce_response(&app, OTHER_ADDRESS).await?.status()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn nonce_requests_from_the_same_ip_are_limited() -> Result<()> {
+    let app = spawn_app_with_limit(
+        NONCE_ROUTE,
+        RouteRateLimit {
+            per_ip: single_request_limit(),
+            per_address: None,
+        },
+    )
+    .await;
+
+    assert_eq!(
+        StatusCode::OK,
+        nonce_response(&app, &app.user_address()).await?.status()
+    );
+    let response = nonce_response(&app, OTHER_ADDRESS).await?;
+
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sign_in_attempts_for_the_same_address_are_limited() -> Result<()> {
+    let app = spawn_app_with_limit(
+        "/web3_auth",
+        RouteRateLimit {
+            per_ip: None,
+            per_address: single_request_limit(),
+        },
+    )
+    .await;
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign(&challenge.message).await?;
+    app.web3_auth(signature.to_string().as_str(), &challenge.message)
+        .await?;
+
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign(&challenge.message).await?;
+    let response = app
+        .web3_auth_response(signature.to_string().as_str(), &challenge.message)
+        .await?;
+
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, response.status());
+
+    Ok(())
+}
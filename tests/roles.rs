@@ -0,0 +1,135 @@
+mod helpers;
+
+use ethers::prelude::{rand, LocalWallet, Signer};
+use eyre::{Result, WrapErr};
+use helpers::{decode_claims, jwt_of, spawn_app, TestApp};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use battlemon_ethereum::{address::ToHex, role::Role, routes::JsonResponse};
+
+async fn grant_role(app: &TestApp, user_id: &str, role: Role) -> Result<()> {
+    sqlx::query!(
+        r#"
+        insert into user_roles(account_id, role)
+        select account_id, $2 from users
+        where user_id = $1
+        "#,
+        user_id,
+        role.to_string()
+    )
+    .execute(&app.db_pool)
+    .await
+    .wrap_err("Failed to grant role")?;
+
+    Ok(())
+}
+
+/// Sign in as a user with admin role.
+async fn login_as_admin(app: &TestApp) -> Result<Value> {
+    app.login().await?;
+    grant_role(app, &app.user_address(), Role::Admin).await?;
+
+    app.login().await
+}
+
+/// Sign up another user, returning the address of its wallet.
+async fn another_user(app: &TestApp) -> Result<String> {
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    app.login_with(&wallet).await?;
+
+    Ok(wallet.address().to_hex())
+}
+
+#[tokio::test]
+async fn new_user_is_player() -> Result<()> {
+    let app = spawn_app().await;
+
+    let tokens = app.login().await?;
+
+    assert_eq!(vec![Role::Player], decode_claims(jwt_of(&tokens))?.roles);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_sets_roles_of_user() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = login_as_admin(&app).await?;
+    let user_id = another_user(&app).await?;
+
+    let json = json!({ "roles": ["player", "moderator"] });
+    let response = app
+        .put_with_bearer(&format!("users/{user_id}/roles"), jwt_of(&tokens), json)
+        .await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let roles = sqlx::query_scalar!(
+        r#"
+        select role from user_roles
+        join users using (account_id)
+        where user_id = $1
+        order by role
+        "#,
+        user_id
+    )
+    .fetch_all(&app.db_pool)
+    .await?;
+    assert_eq!(vec!["moderator", "player"], roles);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn player_is_forbidden_to_set_roles() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+
+    let json = json!({ "roles": ["admin"] });
+    let response = app
+        .put_with_bearer(
+            &format!("users/{}/roles", app.user_address()),
+            jwt_of(&tokens),
+            json,
+        )
+        .await?;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn roles_of_unknown_user_are_not_set() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = login_as_admin(&app).await?;
+    let user_id = LocalWallet::new(&mut rand::thread_rng()).address().to_hex();
+
+    let json = json!({ "roles": ["moderator"] });
+    let response = app
+        .put_with_bearer(&format!("users/{user_id}/roles"), jwt_of(&tokens), json)
+        .await?;
+
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn refreshed_token_contains_new_roles() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    grant_role(&app, &app.user_address(), Role::Service).await?;
+
+    let refresh_token = tokens.get("refresh_token").unwrap().as_str().unwrap();
+    let response = app.refresh_token_response(refresh_token).await?;
+    let JsonResponse::Success(new_tokens) = response.json::<JsonResponse<Value>>().await? else {
+        eyre::bail!("Expected success response");
+    };
+
+    let claims = decode_claims(jwt_of(&new_tokens))?;
+    assert!(claims.has_role(Role::Service));
+    assert!(!claims.has_role(Role::Admin));
+
+    Ok(())
+}
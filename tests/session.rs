@@ -0,0 +1,169 @@
+mod helpers;
+
+use std::collections::HashMap;
+
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use eyre::Result;
+use helpers::{assert_success_status, spawn_app, spawn_app_with_config, TestApp};
+use reqwest::{
+    header::{COOKIE, SET_COOKIE},
+    Client, Response, StatusCode,
+};
+use serde_json::{json, Value};
+
+use battlemon_ethereum::routes::{
+    ACCESS_TOKEN_COOKIE, CSRF_HEADER, CSRF_TOKEN_COOKIE, REFRESH_TOKEN_COOKIE,
+};
+
+/// Sign in asking for the tokens to be set as cookies.
+async fn cookie_login(app: &TestApp) -> Result<Response> {
+    let challenge = app.get_challenge_for_user(&app.user_address()).await?;
+    let signature = app.sign(&challenge.message).await?;
+    let response = Client::new()
+        .post(format!("http://{}/web3_auth", app.address))
+        .json(&json!({
+            "signature": signature.to_string(),
+            "message": challenge.message,
+            "session": "cookie",
+        }))
+        .send()
+        .await?;
+
+    Ok(response)
+}
+
+/// Cookies set by the response, by name.
+fn set_cookies(response: &Response) -> HashMap<String, Cookie<'static>> {
+    response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| Cookie::parse(value.to_owned()).ok())
+        .map(|cookie| (cookie.name().to_owned(), cookie))
+        .collect()
+}
+
+/// Value of `Cookie` header, which the browser sends back.
+fn cookie_header(cookies: &HashMap<String, Cookie<'static>>) -> String {
+    cookies
+        .values()
+        .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[tokio::test]
+async fn cookie_login_sets_http_only_cookies() -> Result<()> {
+    let app = spawn_app().await;
+
+    let response = assert_success_status(cookie_login(&app).await?).await?;
+    let cookies = set_cookies(&response);
+    let body: Value = response.json().await?;
+
+    for name in [ACCESS_TOKEN_COOKIE, REFRESH_TOKEN_COOKIE] {
+        let cookie = &cookies[name];
+        assert_eq!(Some(true), cookie.http_only(), "{name} isn't HttpOnly");
+        assert_eq!(Some(SameSite::Lax), cookie.same_site());
+        assert_eq!(Some("/"), cookie.path());
+    }
+    let csrf_cookie = &cookies[CSRF_TOKEN_COOKIE];
+    assert_ne!(Some(true), csrf_cookie.http_only());
+    assert_eq!(
+        Some(csrf_cookie.value()),
+        body["success"]["csrf_token"].as_str()
+    );
+    assert!(body["success"].get("jwt").is_none());
+    assert!(body["success"].get("refresh_token").is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn access_token_cookie_authenticates_safe_requests() -> Result<()> {
+    let app = spawn_app().await;
+    let response = assert_success_status(cookie_login(&app).await?).await?;
+    let cookies = set_cookies(&response);
+
+    let response = Client::new()
+        .get(format!("http://{}/me", app.address))
+        .header(COOKIE, cookie_header(&cookies))
+        .send()
+        .await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn state_changing_request_by_cookie_requires_csrf_token() -> Result<()> {
+    let app = spawn_app().await;
+    let response = assert_success_status(cookie_login(&app).await?).await?;
+    let cookies = set_cookies(&response);
+    let logout = || {
+        Client::new()
+            .post(format!("http://{}/logout", app.address))
+            .header(COOKIE, cookie_header(&cookies))
+    };
+
+    let without_csrf = logout().send().await?;
+    let wrong_csrf = logout().header(CSRF_HEADER, "wrong").send().await?;
+    let with_csrf = logout()
+        .header(CSRF_HEADER, cookies[CSRF_TOKEN_COOKIE].value())
+        .send()
+        .await?;
+
+    assert_eq!(StatusCode::FORBIDDEN, without_csrf.status());
+    assert_eq!(StatusCode::FORBIDDEN, wrong_csrf.status());
+    assert_eq!(StatusCode::OK, with_csrf.status());
+    let removed = set_cookies(&with_csrf);
+    assert_eq!("", removed[ACCESS_TOKEN_COOKIE].value());
+    assert_eq!("", removed[REFRESH_TOKEN_COOKIE].value());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn refresh_token_cookie_is_exchanged_for_new_cookies() -> Result<()> {
+    let app = spawn_app().await;
+    let response = assert_success_status(cookie_login(&app).await?).await?;
+    let cookies = set_cookies(&response);
+    let refresh = || {
+        Client::new()
+            .post(format!("http://{}/token/refresh", app.address))
+            .header(COOKIE, cookie_header(&cookies))
+    };
+
+    let without_csrf = refresh().send().await?;
+    let with_csrf = refresh()
+        .header(CSRF_HEADER, cookies[CSRF_TOKEN_COOKIE].value())
+        .send()
+        .await?;
+
+    assert_eq!(StatusCode::FORBIDDEN, without_csrf.status());
+    assert_eq!(StatusCode::OK, with_csrf.status());
+    let new_cookies = set_cookies(&with_csrf);
+    assert_ne!(
+        cookies[REFRESH_TOKEN_COOKIE].value(),
+        new_cookies[REFRESH_TOKEN_COOKIE].value()
+    );
+    assert_ne!(
+        cookies[CSRF_TOKEN_COOKIE].value(),
+        new_cookies[CSRF_TOKEN_COOKIE].value()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cookie_session_is_rejected_when_disabled() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.session.cookies_enabled = false).await;
+
+    let response = cookie_login(&app).await?;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    assert!(set_cookies(&response).is_empty());
+
+    Ok(())
+}
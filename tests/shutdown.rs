@@ -0,0 +1,58 @@
+mod helpers;
+
+use std::{collections::HashMap, time::Duration};
+
+use eyre::Result;
+use helpers::{spawn_app_with_config, spawn_mock_rpc, TestApp};
+use reqwest::StatusCode;
+use serde_json::json;
+
+use battlemon_ethereum::config::EthereumConfig;
+
+/// Spawn app, whose readiness check waits for the Ethereum node for `delay`.
+async fn spawn_app_with_slow_readiness(delay: Duration, drain_timeout_secs: u64) -> TestApp {
+    let rpc_url = spawn_mock_rpc(HashMap::from([("eth_blockNumber", json!("0x1"))]), delay);
+
+    spawn_app_with_config(|config| {
+        config.app.drain_timeout_secs = drain_timeout_secs;
+        config.ethereum = Some(EthereumConfig {
+            rpc_url,
+            request_timeout_ms: 10_000,
+        });
+    })
+    .await
+}
+
+#[tokio::test]
+async fn in_flight_request_completes_during_shutdown() -> Result<()> {
+    let app = spawn_app_with_slow_readiness(Duration::from_secs(1), 30).await;
+    let url = format!("http://{}/health/ready", app.address);
+    let in_flight = tokio::spawn(reqwest::get(url.clone()));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    app.shutdown.cancel();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let new_request = reqwest::get(url).await;
+
+    assert!(new_request.is_err(), "New connections are still accepted");
+    let response = in_flight.await??;
+    assert_eq!(StatusCode::OK, response.status());
+    tokio::time::timeout(Duration::from_secs(5), app.server).await???;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn shutdown_does_not_wait_longer_than_drain_timeout() -> Result<()> {
+    let app = spawn_app_with_slow_readiness(Duration::from_secs(30), 1).await;
+    let url = format!("http://{}/health/ready", app.address);
+    let _in_flight = tokio::spawn(reqwest::get(url));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    app.shutdown.cancel();
+
+    let stopped = tokio::time::timeout(Duration::from_secs(10), app.server).await;
+    assert!(stopped.is_ok(), "App hasn't stopped after drain timeout");
+
+    Ok(())
+}
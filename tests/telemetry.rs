@@ -0,0 +1,123 @@
+mod helpers;
+
+use std::{
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{body::Bytes, extract::State, routing::post, Router};
+use ethers::utils::hex;
+use eyre::{bail, Result};
+use helpers::spawn_app;
+use opentelemetry_proto::tonic::{
+    collector::trace::v1::ExportTraceServiceRequest, common::v1::any_value, trace::v1::Span,
+};
+use prost::Message;
+
+use battlemon_ethereum::{
+    config::OtlpConfig,
+    telemetry::{build_subscriber, otlp_tracer},
+};
+
+type ReceivedSpans = Arc<Mutex<Vec<Span>>>;
+
+/// Stand-in for OpenTelemetry collector, which keeps spans exported over OTLP/HTTP.
+/// Returns url of the collector.
+fn spawn_collector(spans: ReceivedSpans) -> String {
+    let router = Router::new()
+        .route(
+            "/v1/traces",
+            post(
+                |State(spans): State<ReceivedSpans>, body: Bytes| async move {
+                    let request = ExportTraceServiceRequest::decode(body).unwrap();
+                    let received = request
+                        .resource_spans
+                        .into_iter()
+                        .flat_map(|resource_spans| resource_spans.scope_spans)
+                        .flat_map(|scope_spans| scope_spans.spans);
+                    spans.lock().unwrap().extend(received);
+                },
+            ),
+        )
+        .with_state(spans);
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind address for collector");
+    let address = listener.local_addr().unwrap();
+    let server = axum::Server::from_tcp(listener)
+        .expect("Failed to create collector server")
+        .serve(router.into_make_service());
+    tokio::spawn(server);
+
+    format!("http://{address}")
+}
+
+fn string_attribute<'a>(span: &'a Span, key: &str) -> Option<&'a str> {
+    span.attributes
+        .iter()
+        .find(|attribute| attribute.key == key)
+        .and_then(|attribute| attribute.value.as_ref()?.value.as_ref())
+        .and_then(|value| match value {
+            any_value::Value::StringValue(value) => Some(value.as_str()),
+            _ => None,
+        })
+}
+
+#[tokio::test]
+async fn request_span_continues_trace_of_caller() -> Result<()> {
+    // Export spans shortly after they end.
+    std::env::set_var("OTEL_BSP_SCHEDULE_DELAY", "100");
+    let spans = ReceivedSpans::default();
+    let tracer = otlp_tracer(&OtlpConfig {
+        endpoint: spawn_collector(spans.clone()),
+        service_name: "test".to_owned(),
+        sampling_ratio: 0.0,
+    })?;
+    // Test runtime is single threaded, so the server runs with this subscriber as well.
+    let _guard = tracing::subscriber::set_default(build_subscriber(
+        "test".to_owned(),
+        "info".to_owned(),
+        std::io::sink,
+        Some(tracer),
+    ));
+    let app = spawn_app().await;
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let parent_span_id = "00f067aa0ba902b7";
+
+    let response = reqwest::Client::new()
+        .get(format!("http://{}/healthcheck", app.address))
+        .header("traceparent", format!("00-{trace_id}-{parent_span_id}-01"))
+        .bearer_auth("secret-token")
+        .header("cookie", "refresh_token=secret-cookie")
+        .send()
+        .await?;
+    let request_id = response.headers()["x-request-id"].to_str()?.to_owned();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let request_span = spans
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|span| span.name == "request")
+            .cloned();
+        if let Some(span) = request_span {
+            // Sampled by the caller, even though the service doesn't sample own traces.
+            assert_eq!(trace_id, hex::encode(&span.trace_id));
+            assert_eq!(parent_span_id, hex::encode(&span.parent_span_id));
+            assert_eq!(
+                Some(request_id.as_str()),
+                string_attribute(&span, "request_id")
+            );
+            // Credentials are redacted, while the rest of headers is recorded.
+            let headers = string_attribute(&span, "headers").unwrap();
+            assert!(headers.contains("traceparent"));
+            assert!(!headers.contains("secret"));
+
+            return Ok(());
+        }
+        if Instant::now() > deadline {
+            bail!("Request span hasn't been exported");
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
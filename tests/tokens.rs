@@ -0,0 +1,310 @@
+mod helpers;
+
+use eyre::{Result, WrapErr};
+use helpers::{
+    assert_success_status, decode_claims, jwt_of, refresh_token_of, spawn_app,
+    spawn_app_with_config,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use battlemon_ethereum::{refresh_token::hash_refresh_token, routes::JsonResponse};
+
+async fn success_json(response: reqwest::Response) -> Result<Value> {
+    let response = assert_success_status(response).await?;
+    let JsonResponse::Success(value) = response.json().await? else {
+        eyre::bail!("Failed to deserialize json from body");
+    };
+
+    Ok(value)
+}
+
+#[tokio::test]
+async fn web3_auth_issues_refresh_token() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let token_hash = hash_refresh_token(refresh_token_of(&tokens));
+
+    let user_id = sqlx::query_scalar!(
+        r#"
+        select user_id from refresh_tokens
+        where token_hash = $1
+        "#,
+        token_hash
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .wrap_err("Failed to fetch stored refresh token")?;
+
+    assert_eq!(app.user_address(), user_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn refreshed_token_keeps_chain_id() -> Result<()> {
+    let app = spawn_app().await;
+    let challenge = app
+        .get_challenge_for_user_on_chain(&app.user_address(), 137)
+        .await?;
+    let signature = app.sign(&challenge.message).await?;
+    let tokens = app
+        .web3_auth(signature.to_string().as_str(), &challenge.message)
+        .await?;
+
+    let response = app
+        .refresh_token_response(refresh_token_of(&tokens))
+        .await?;
+    let new_tokens = success_json(response).await?;
+
+    assert_eq!(137, decode_claims(jwt_of(&new_tokens))?.chain_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn refresh_token_is_rotated() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let refresh_token = refresh_token_of(&tokens);
+
+    let response = app.refresh_token_response(refresh_token).await?;
+    let new_tokens = success_json(response).await?;
+
+    assert!(new_tokens.get("jwt").unwrap().is_string());
+    assert_ne!(refresh_token, refresh_token_of(&new_tokens));
+
+    let response = app
+        .refresh_token_response(refresh_token_of(&new_tokens))
+        .await?;
+    success_json(response).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reuse_of_rotated_refresh_token_revokes_family() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let stolen_token = refresh_token_of(&tokens);
+    let response = app.refresh_token_response(stolen_token).await?;
+    let new_tokens = success_json(response).await?;
+
+    let response = app.refresh_token_response(stolen_token).await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response
+        .text()
+        .await?
+        .contains("Refresh token has been already used"));
+
+    let response = app
+        .refresh_token_response(refresh_token_of(&new_tokens))
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response
+        .text()
+        .await?
+        .contains("Refresh token is invalid or revoked"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reuse_detection_leaves_other_families_intact() -> Result<()> {
+    let app = spawn_app().await;
+    let first_session = app.login().await?;
+    let second_session = app.login().await?;
+    let stolen_token = refresh_token_of(&first_session);
+    app.refresh_token_response(stolen_token).await?;
+    app.refresh_token_response(stolen_token).await?;
+
+    let response = app
+        .refresh_token_response(refresh_token_of(&second_session))
+        .await?;
+    success_json(response).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn expired_refresh_token_is_rejected() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let refresh_token = refresh_token_of(&tokens);
+    sqlx::query!(
+        r#"
+        update refresh_tokens set expires_at = now() - interval '1 second'
+        where token_hash = $1
+        "#,
+        hash_refresh_token(refresh_token)
+    )
+    .execute(&app.db_pool)
+    .await
+    .wrap_err("Failed to expire refresh token")?;
+
+    let response = app.refresh_token_response(refresh_token).await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response.text().await?.contains("Refresh token is expired"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_refresh_token_is_rejected() -> Result<()> {
+    let app = spawn_app().await;
+
+    let response = app.refresh_token_response("unknown").await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn logout_revokes_access_token() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let jwt = jwt_of(&tokens);
+
+    let response = app.post_with_bearer::<()>("logout", jwt, None).await?;
+    assert_success_status(response).await?;
+
+    let response = app.post_with_bearer::<()>("logout", jwt, None).await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response.text().await?.contains("Revoked auth token"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn logout_revokes_passed_refresh_token() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let json = json!({ "refresh_token": refresh_token_of(&tokens) });
+
+    let response = app
+        .post_with_bearer("logout", jwt_of(&tokens), Some(json))
+        .await?;
+    assert_success_status(response).await?;
+
+    let response = app
+        .refresh_token_response(refresh_token_of(&tokens))
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn revoked_tokens_are_stored_in_database() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    app.post_with_bearer::<()>("logout", jwt_of(&tokens), None)
+        .await?;
+
+    let account_id = sqlx::query_scalar!(
+        r#"
+        select account_id from revoked_tokens
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .wrap_err("Failed to fetch revoked token")?;
+
+    assert_eq!(decode_claims(jwt_of(&tokens))?.sub, account_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn revocations_of_expired_tokens_are_swept() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.nonce.sweep_interval_secs = 1).await;
+    let expired_tokens = app.login().await?;
+    let tokens = app.login().await?;
+    for tokens in [&expired_tokens, &tokens] {
+        app.post_with_bearer::<()>("logout", jwt_of(tokens), None)
+            .await?;
+    }
+    sqlx::query!(
+        r#"
+        update revoked_tokens set expires_at = now() - interval '1 minute'
+        where jti = $1
+        "#,
+        decode_claims(jwt_of(&expired_tokens))?.jti
+    )
+    .execute(&app.db_pool)
+    .await?;
+
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    let remaining = sqlx::query_scalar!(
+        r#"
+        select jti from revoked_tokens
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await?;
+
+    assert_eq!(vec![decode_claims(jwt_of(&tokens))?.jti], remaining);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn revoke_endpoint_revokes_access_token() -> Result<()> {
+    let app = spawn_app().await;
+    let first_session = app.login().await?;
+    let second_session = app.login().await?;
+    let json = json!({ "token": jwt_of(&second_session) });
+
+    let response = app
+        .post_with_bearer("tokens/revoke", jwt_of(&first_session), Some(json))
+        .await?;
+    assert_success_status(response).await?;
+
+    let response = app
+        .post_with_bearer::<()>("logout", jwt_of(&second_session), None)
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    let response = app
+        .post_with_bearer::<()>("logout", jwt_of(&first_session), None)
+        .await?;
+    assert_success_status(response).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn revoke_endpoint_revokes_refresh_token() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let json = json!({ "token": refresh_token_of(&tokens) });
+
+    let response = app
+        .post_with_bearer("tokens/revoke", jwt_of(&tokens), Some(json))
+        .await?;
+    assert_success_status(response).await?;
+
+    let response = app
+        .refresh_token_response(refresh_token_of(&tokens))
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn revoke_endpoint_requires_auth_token() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let json = json!({ "token": jwt_of(&tokens) });
+
+    let response = app
+        .post_with_bearer("tokens/revoke", "invalid", Some(json))
+        .await?;
+
+    assert!(response.status().is_client_error());
+
+    Ok(())
+}
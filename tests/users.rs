@@ -0,0 +1,168 @@
+mod helpers;
+
+use ethers::prelude::{rand, LocalWallet, Signer};
+use eyre::{bail, Result};
+use helpers::{jwt_of, profile_of, spawn_app, TestApp};
+use reqwest::{Response, StatusCode};
+use rstest::rstest;
+use serde_json::{json, Value};
+
+use battlemon_ethereum::{address::ToHex, routes::JsonResponse};
+
+async fn update_me(app: &TestApp, jwt: &str, json: Value) -> Result<Response> {
+    app.patch_with_bearer("me", jwt, json).await
+}
+
+#[tokio::test]
+async fn me_returns_profile_of_authenticated_user() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+
+    let response = app.get_with_bearer("me", jwt_of(&tokens)).await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let profile = profile_of(response).await?;
+    assert_eq!(app.user_address(), profile.wallets[0].address);
+    assert_eq!(None, profile.wallets[0].ens_name);
+    assert_eq!(None, profile.nickname);
+    assert!(profile.last_login_at.is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn me_requires_auth_token() -> Result<()> {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("http://{}/me", app.address)).await?;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn profile_is_updated() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+
+    let json = json!({
+        "nickname": "Player_1",
+        "avatar_url": "https://example.com/avatar.png",
+    });
+    let response = update_me(&app, jwt_of(&tokens), json).await?;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let json = json!({ "avatar_url": "https://example.com/new_avatar.png" });
+    update_me(&app, jwt_of(&tokens), json).await?;
+
+    let response = app
+        .get(&format!("users/{}", app.user_address()), None)
+        .await?;
+    let profile = profile_of(response).await?;
+    assert_eq!(Some("Player_1"), profile.nickname.as_deref());
+    assert_eq!(
+        Some("https://example.com/new_avatar.png"),
+        profile.avatar_url.as_deref()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn profile_fields_are_cleared_by_null() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let json = json!({
+        "nickname": "Player_1",
+        "avatar_url": "https://example.com/avatar.png",
+    });
+    update_me(&app, jwt_of(&tokens), json).await?;
+
+    let response = update_me(&app, jwt_of(&tokens), json!({ "avatar_url": null })).await?;
+
+    let profile = profile_of(response).await?;
+    assert_eq!(Some("Player_1"), profile.nickname.as_deref());
+    assert_eq!(None, profile.avatar_url);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn public_profile_hides_last_login() -> Result<()> {
+    let app = spawn_app().await;
+    app.login().await?;
+
+    let response = app
+        .get(&format!("users/{}", app.user_address()), None)
+        .await?;
+
+    let JsonResponse::Success(profile) = response.json::<JsonResponse<Value>>().await? else {
+        bail!("Expected success response");
+    };
+    assert!(profile.get("last_login_at").is_none());
+    assert!(profile.get("created_at").is_some());
+
+    Ok(())
+}
+
+#[rstest]
+#[case::short_nickname(json!({ "nickname": "ab" }))]
+#[case::long_nickname(json!({ "nickname": "a".repeat(33) }))]
+#[case::nickname_with_spaces(json!({ "nickname": "bad nickname" }))]
+#[case::malformed_avatar_url(json!({ "avatar_url": "not a url" }))]
+#[case::avatar_url_with_foreign_scheme(json!({ "avatar_url": "javascript:alert(1)" }))]
+#[tokio::test]
+async fn invalid_profile_is_rejected(#[case] json: Value) -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+
+    let response = update_me(&app, jwt_of(&tokens), json).await?;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn nickname_must_be_unique() -> Result<()> {
+    let app = spawn_app().await;
+    sqlx::query!(
+        r#"
+        insert into accounts(account_id, nickname)
+        values (gen_random_uuid(), 'taken')
+        "#
+    )
+    .execute(&app.db_pool)
+    .await?;
+    let tokens = app.login().await?;
+
+    let response = update_me(&app, jwt_of(&tokens), json!({ "nickname": "Taken" })).await?;
+
+    assert_eq!(StatusCode::CONFLICT, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_user_is_not_found() -> Result<()> {
+    let app = spawn_app().await;
+    let user_id = LocalWallet::new(&mut rand::thread_rng()).address().to_hex();
+
+    let response = reqwest::get(format!("http://{}/users/{user_id}", app.address)).await?;
+
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn malformed_user_id_is_rejected() -> Result<()> {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("http://{}/users/not_an_address", app.address)).await?;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    Ok(())
+}
@@ -0,0 +1,295 @@
+mod helpers;
+
+use ethers::prelude::{rand, LocalWallet, Signer};
+use eyre::{bail, Result, WrapErr};
+use helpers::{decode_claims, jwt_of, profile_of, refresh_token_of, spawn_app, TestApp};
+use reqwest::{Method, Response, StatusCode};
+use serde_json::json;
+
+use battlemon_ethereum::{
+    address::ToHex,
+    routes::{Challenge, JsonResponse, Profile},
+};
+
+fn addresses_of(profile: &Profile) -> Vec<String> {
+    profile
+        .wallets
+        .iter()
+        .map(|wallet| wallet.address.clone())
+        .collect()
+}
+
+/// Challenge to link the wallet to the account of the token.
+async fn link_challenge(
+    app: &TestApp,
+    jwt: &str,
+    wallet: &LocalWallet,
+    mode: &str,
+) -> Result<Challenge> {
+    let response = app
+        .get_with_bearer(
+            &format!("me/wallets/{}/nonce?mode={mode}", wallet.address().to_hex()),
+            jwt,
+        )
+        .await?;
+    let JsonResponse::Success(challenge) = response.json().await? else {
+        bail!("Expected success response");
+    };
+
+    Ok(challenge)
+}
+
+/// Link the wallet to the account of the token, signing the link challenge with the wallet.
+async fn link_wallet(app: &TestApp, jwt: &str, wallet: &LocalWallet) -> Result<Response> {
+    let challenge = link_challenge(app, jwt, wallet, "personal_sign").await?;
+    let message = challenge.message.unwrap();
+    let signature = wallet.sign_message(&message).await?;
+    let json = json!({
+        "message": message,
+        "signature": signature.to_string(),
+    });
+
+    app.post_with_bearer("me/wallets", jwt, Some(json)).await
+}
+
+async fn unlink_wallet(app: &TestApp, jwt: &str, user_id: &str) -> Result<Response> {
+    reqwest::Client::new()
+        .request(
+            Method::DELETE,
+            format!("http://{}/me/wallets/{user_id}", app.address),
+        )
+        .bearer_auth(jwt)
+        .send()
+        .await
+        .wrap_err("Failed to make request")
+}
+
+#[tokio::test]
+async fn linked_wallet_signs_in_to_the_same_account() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+
+    let response = link_wallet(&app, jwt_of(&tokens), &wallet).await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let profile = profile_of(response).await?;
+    let addresses = addresses_of(&profile);
+    assert!(addresses.contains(&wallet.address().to_hex()));
+    assert_eq!(2, addresses.len());
+
+    let linked_wallet_tokens = app.login_with(&wallet).await?;
+    let claims = decode_claims(jwt_of(&linked_wallet_tokens))?;
+    assert_eq!(decode_claims(jwt_of(&tokens))?.sub, claims.sub);
+    assert_eq!(wallet.address().to_hex(), claims.wallet);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_of_another_account_is_not_linked() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    app.login_with(&wallet).await?;
+
+    let response = link_wallet(&app, jwt_of(&tokens), &wallet).await?;
+
+    assert_eq!(StatusCode::CONFLICT, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_is_linked_only_with_its_signature() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    let challenge = link_challenge(&app, jwt_of(&tokens), &wallet, "personal_sign").await?;
+    let message = challenge.message.unwrap();
+    let signature = app.sign(&message).await?;
+    let json = json!({
+        "message": message,
+        "signature": signature.to_string(),
+    });
+
+    let response = app
+        .post_with_bearer("me/wallets", jwt_of(&tokens), Some(json))
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn link_challenge_names_the_account() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    let account_id = decode_claims(jwt_of(&tokens))?.sub;
+
+    let challenge = link_challenge(&app, jwt_of(&tokens), &wallet, "personal_sign").await?;
+    assert!(challenge.message.unwrap().contains(&format!(
+        "Link this wallet to Battlemon account {account_id}"
+    )));
+
+    let challenge = link_challenge(&app, jwt_of(&tokens), &wallet, "typed_data").await?;
+    let typed_data = challenge.typed_data.unwrap();
+    assert_eq!("LinkWallet", typed_data.primary_type);
+    assert_eq!(account_id.to_string(), typed_data.message["account"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_is_linked_with_typed_data_signature() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    let challenge = link_challenge(&app, jwt_of(&tokens), &wallet, "typed_data").await?;
+    let signature = wallet
+        .sign_typed_data(&challenge.typed_data.unwrap())
+        .await?;
+    let json = json!({
+        "mode": "typed_data",
+        "signature": signature.to_string(),
+        "user_id": wallet.address().to_hex(),
+        "nonce": challenge.nonce,
+    });
+
+    let response = app
+        .post_with_bearer("me/wallets", jwt_of(&tokens), Some(json))
+        .await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(addresses_of(&profile_of(response).await?).contains(&wallet.address().to_hex()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sign_in_signature_does_not_link_wallet() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    let challenge = app
+        .get_challenge_for_user(&wallet.address().to_hex())
+        .await?;
+    let signature = wallet.sign_message(&challenge.message).await?;
+    let json = json!({
+        "message": challenge.message,
+        "signature": signature.to_string(),
+    });
+
+    let response = app
+        .post_with_bearer("me/wallets", jwt_of(&tokens), Some(json))
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn link_signature_does_not_sign_in() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    let challenge = link_challenge(&app, jwt_of(&tokens), &wallet, "personal_sign").await?;
+    let message = challenge.message.unwrap();
+    let signature = wallet.sign_message(&message).await?;
+
+    let response = app
+        .web3_auth_response(&signature.to_string(), &message)
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn link_challenge_of_another_account_does_not_link_wallet() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let other_tokens = app
+        .login_with(&LocalWallet::new(&mut rand::thread_rng()))
+        .await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    let challenge = link_challenge(&app, jwt_of(&other_tokens), &wallet, "personal_sign").await?;
+    let message = challenge.message.unwrap();
+    let signature = wallet.sign_message(&message).await?;
+    let json = json!({
+        "message": message,
+        "signature": signature.to_string(),
+    });
+
+    let response = app
+        .post_with_bearer("me/wallets", jwt_of(&tokens), Some(json))
+        .await?;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn last_wallet_is_not_unlinked() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+
+    let response = unlink_wallet(&app, jwt_of(&tokens), &app.user_address()).await?;
+
+    assert_eq!(StatusCode::CONFLICT, response.status());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unlinked_wallet_loses_access_to_the_account() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    link_wallet(&app, jwt_of(&tokens), &wallet).await?;
+    let linked_wallet_tokens = app.login_with(&wallet).await?;
+
+    let response = unlink_wallet(&app, jwt_of(&tokens), &wallet.address().to_hex()).await?;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let profile = profile_of(response).await?;
+    assert_eq!(vec![app.user_address()], addresses_of(&profile));
+
+    let response = app
+        .refresh_token_response(refresh_token_of(&linked_wallet_tokens))
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let response = app
+        .get_with_bearer("me", jwt_of(&linked_wallet_tokens))
+        .await?;
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    let response = app.get_with_bearer("me", jwt_of(&tokens)).await?;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let new_account_tokens = app.login_with(&wallet).await?;
+    assert_ne!(
+        decode_claims(jwt_of(&tokens))?.sub,
+        decode_claims(jwt_of(&new_account_tokens))?.sub
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_of_another_account_is_not_unlinked() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    app.login_with(&wallet).await?;
+
+    let response = unlink_wallet(&app, jwt_of(&tokens), &wallet.address().to_hex()).await?;
+
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+mod helpers;
+
+use battlemon_ethereum::{jwt::Claims, routes::OpenIdConfiguration};
+use eyre::Result;
+use helpers::spawn_app;
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+
+#[tokio::test]
+async fn issued_token_is_verifiable_with_published_jwks() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let jwt = tokens.get("jwt").unwrap().as_str().unwrap();
+
+    let response = app.get(".well-known/jwks.json", None).await?;
+    assert!(response
+        .headers()
+        .get("cache-control")
+        .unwrap()
+        .to_str()?
+        .contains("max-age"));
+    let jwk_set: JwkSet = response.json().await?;
+    assert_eq!(1, jwk_set.keys.len());
+
+    let kid = jsonwebtoken::decode_header(jwt)?.kid.unwrap();
+    let jwk = jwk_set.find(&kid).unwrap();
+    let decoding_key = DecodingKey::from_jwk(jwk)?;
+    let claims =
+        jsonwebtoken::decode::<Claims>(jwt, &decoding_key, &Validation::new(Algorithm::EdDSA))?
+            .claims;
+
+    assert_eq!(app.user_address(), claims.wallet);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn openid_configuration_describes_issuer() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let jwt = tokens.get("jwt").unwrap().as_str().unwrap();
+
+    let configuration: OpenIdConfiguration = app
+        .get(".well-known/openid-configuration", None)
+        .await?
+        .json()
+        .await?;
+
+    let mut validation = Validation::new(Algorithm::EdDSA);
+    validation.insecure_disable_signature_validation();
+    validation.set_issuer(&[&configuration.issuer]);
+    jsonwebtoken::decode::<Claims>(jwt, &DecodingKey::from_secret(&[]), &validation)?;
+
+    assert!(configuration.jwks_uri.ends_with("/.well-known/jwks.json"));
+    assert_eq!(
+        vec!["EdDSA".to_owned()],
+        configuration.id_token_signing_alg_values_supported
+    );
+
+    Ok(())
+}
@@ -0,0 +1,232 @@
+mod helpers;
+
+use std::time::Duration;
+
+use ethers::prelude::{rand, LocalWallet};
+use eyre::{bail, eyre, Result};
+use futures_util::{SinkExt, StreamExt};
+use helpers::{
+    assert_success_status, decode_claims, jwt_of, sign_claims, spawn_app, spawn_app_with_config,
+    TestApp,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{
+        client::IntoClientRequest,
+        http::header::{AUTHORIZATION, COOKIE},
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Error as WsError, Message,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Upper bound of waiting for a message of the server.
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn connect(app: &TestApp, token: Option<&str>) -> Result<Socket, WsError> {
+    let mut request = format!("ws://{}/ws", app.address).into_client_request()?;
+    if let Some(token) = token {
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+    }
+    let (socket, _) = connect_async(request).await?;
+
+    Ok(socket)
+}
+
+/// Next message of the server, skipping heartbeats.
+async fn receive(socket: &mut Socket) -> Result<Message> {
+    loop {
+        let message = tokio::time::timeout(RECEIVE_TIMEOUT, socket.next())
+            .await?
+            .ok_or_else(|| eyre!("Socket has been closed"))??;
+        if !matches!(message, Message::Ping(_) | Message::Pong(_)) {
+            return Ok(message);
+        }
+    }
+}
+
+async fn receive_json(socket: &mut Socket) -> Result<Value> {
+    match receive(socket).await? {
+        Message::Text(text) => Ok(serde_json::from_str(&text)?),
+        message => bail!("Unexpected message {message:?}"),
+    }
+}
+
+async fn receive_close(socket: &mut Socket) -> Result<CloseFrame<'static>> {
+    match receive(socket).await? {
+        Message::Close(Some(frame)) => Ok(frame),
+        message => bail!("Unexpected message {message:?}"),
+    }
+}
+
+#[tokio::test]
+async fn profile_updates_are_pushed_to_socket_authenticated_by_header() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let mut socket = connect(&app, Some(jwt_of(&tokens))).await?;
+    let authenticated = receive_json(&mut socket).await?;
+
+    let json = json!({ "nickname": "satoshi" });
+    let response = app.patch_with_bearer("me", jwt_of(&tokens), json).await?;
+    assert_success_status(response).await?;
+    let event = receive_json(&mut socket).await?;
+
+    assert_eq!("authenticated", authenticated["type"]);
+    assert_eq!("profile_updated", event["type"]);
+    assert_eq!("satoshi", event["nickname"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn socket_authenticated_by_first_message_is_closed_on_logout() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let mut socket = connect(&app, None).await?;
+    let authenticate = json!({ "type": "authenticate", "token": jwt_of(&tokens) });
+    socket.send(Message::Text(authenticate.to_string())).await?;
+    let authenticated = receive_json(&mut socket).await?;
+
+    let response = app
+        .post_with_bearer::<()>("logout", jwt_of(&tokens), None)
+        .await?;
+    assert_success_status(response).await?;
+    let event = receive_json(&mut socket).await?;
+    let frame = receive_close(&mut socket).await?;
+
+    assert_eq!("authenticated", authenticated["type"]);
+    assert_eq!("session_revoked", event["type"]);
+    assert_eq!(CloseCode::Policy, frame.code);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn events_of_other_accounts_are_not_pushed() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let other_wallet = LocalWallet::new(&mut rand::thread_rng());
+    let other_tokens = app.login_with(&other_wallet).await?;
+    let mut socket = connect(&app, Some(jwt_of(&tokens))).await?;
+    receive_json(&mut socket).await?;
+
+    let response = app
+        .patch_with_bearer(
+            "me",
+            jwt_of(&other_tokens),
+            json!({ "nickname": "vitalik" }),
+        )
+        .await?;
+    assert_success_status(response).await?;
+    socket
+        .send(Message::Text(json!({ "type": "ping" }).to_string()))
+        .await?;
+    let message = receive_json(&mut socket).await?;
+
+    assert_eq!("pong", message["type"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn upgrade_with_invalid_token_is_rejected() -> Result<()> {
+    let app = spawn_app().await;
+
+    let error = connect(&app, Some("invalid")).await.unwrap_err();
+
+    let WsError::Http(response) = error else {
+        bail!("Unexpected error {error:?}");
+    };
+    assert_eq!(StatusCode::BAD_REQUEST.as_u16(), response.status().as_u16());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn socket_without_token_is_closed_after_auth_timeout() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.websocket.auth_timeout_secs = 1).await;
+    let mut socket = connect(&app, None).await?;
+
+    let frame = receive_close(&mut socket).await?;
+
+    assert_eq!(CloseCode::Policy, frame.code);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cookie_does_not_authenticate_socket() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.websocket.auth_timeout_secs = 1).await;
+    let tokens = app.login().await?;
+    let mut request = format!("ws://{}/ws", app.address).into_client_request()?;
+    request
+        .headers_mut()
+        .insert(COOKIE, format!("access_token={}", jwt_of(&tokens)).parse()?);
+    let (mut socket, _) = connect_async(request).await?;
+
+    let frame = receive_close(&mut socket).await?;
+
+    assert_eq!(CloseCode::Policy, frame.code);
+    assert_eq!("Token hasn't been sent in time", frame.reason);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn socket_is_closed_once_token_expires() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let mut claims = decode_claims(jwt_of(&tokens))?;
+    claims.exp = chrono::Utc::now().timestamp() + 2;
+    let token = sign_claims(&claims)?;
+    let mut socket = connect(&app, Some(&token)).await?;
+    let authenticated = receive_json(&mut socket).await?;
+
+    let frame = receive_close(&mut socket).await?;
+
+    assert_eq!("authenticated", authenticated["type"]);
+    assert_eq!(CloseCode::Policy, frame.code);
+    assert_eq!("Token has expired", frame.reason);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn socket_is_closed_on_shutdown() -> Result<()> {
+    let app = spawn_app().await;
+    let tokens = app.login().await?;
+    let mut socket = connect(&app, Some(jwt_of(&tokens))).await?;
+    receive_json(&mut socket).await?;
+
+    app.shutdown.cancel();
+    let frame = receive_close(&mut socket).await?;
+
+    assert_eq!(CloseCode::Away, frame.code);
+    assert_eq!("Server is shutting down", frame.reason);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_sends_heartbeats() -> Result<()> {
+    let app = spawn_app_with_config(|config| config.websocket.heartbeat_interval_secs = 1).await;
+    let tokens = app.login().await?;
+    let mut socket = connect(&app, Some(jwt_of(&tokens))).await?;
+    receive_json(&mut socket).await?;
+
+    let message = tokio::time::timeout(RECEIVE_TIMEOUT, socket.next()).await?;
+
+    assert!(
+        matches!(message, Some(Ok(Message::Ping(_)))),
+        "Unexpected message {message:?}"
+    );
+
+    Ok(())
+}